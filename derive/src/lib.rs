@@ -0,0 +1,34 @@
+//! Derive macros of `learnopengl-rs`, re-exported next to the traits they implement.
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod std140;
+mod vertex;
+
+/// Derive `learnopengl_rs::ubo::Std140`, laying out the fields of a struct with the std140 rules
+/// of uniform blocks. Every field type must implement `Std140`.
+#[proc_macro_derive(Std140)]
+pub fn derive_std140(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    std140::derive(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Derive `learnopengl_rs::vao::Vertex`, describing each field of a struct as a vertex attribute
+/// of the same type at the field's offset. Every field type must implement `VertexAttribute`.
+///
+/// Attributes get consecutive locations from 0, matrices taking a location per column.
+/// `#[location = N]` sets the location of a field and of the following ones, `#[normalized]`
+/// maps integer fields to [0, 1] or [-1, 1]. `#[integer]` and `#[double]` fields are read as
+/// they are by `int`/`ivecN`/`uvecN` and `double`/`dvecN` inputs rather than converted to floats.
+/// `#[divisor = N]` on the struct makes it a per-instance type, whose attributes advance once
+/// every N instances.
+#[proc_macro_derive(Vertex, attributes(location, normalized, integer, double, divisor))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    vertex::derive(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
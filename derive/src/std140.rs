@@ -0,0 +1,57 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Index, Member, Result};
+
+pub fn derive(input: &DeriveInput) -> Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => return Err(Error::new_spanned(input, "Std140 can only be derived for structs")),
+    };
+    let members: Vec<Member> = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        })
+        .collect();
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let std140 = quote!(::learnopengl_rs::ubo::Std140);
+    let align_to = quote!(::learnopengl_rs::ubo::align_to);
+
+    Ok(quote! {
+        impl #impl_generics #std140 for #name #ty_generics #where_clause {
+            // The alignment of a structure is the largest of its members, rounded up to a vec4
+            const ALIGNMENT: usize = {
+                let mut alignment = 16;
+                #(
+                    if <#types as #std140>::ALIGNMENT > alignment {
+                        alignment = <#types as #std140>::ALIGNMENT;
+                    }
+                )*
+                alignment
+            };
+
+            const SIZE: usize = {
+                let mut offset = 0;
+                #(
+                    offset = #align_to(offset, <#types as #std140>::ALIGNMENT) + <#types as #std140>::SIZE;
+                )*
+                #align_to(offset, <Self as #std140>::ALIGNMENT)
+            };
+
+            fn write_std140(&self, buf: &mut [u8]) {
+                let mut offset = 0;
+                #(
+                    offset = #align_to(offset, <#types as #std140>::ALIGNMENT);
+                    #std140::write_std140(&self.#members, &mut buf[offset..offset + <#types as #std140>::SIZE]);
+                    offset += <#types as #std140>::SIZE;
+                )*
+                let _ = offset;
+            }
+        }
+    })
+}
@@ -0,0 +1,152 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, DeriveInput, Error, Expr, ExprLit, Index, Lit, Member, Result};
+
+/// Value of a `#[name = N]` attribute.
+fn int_attribute(attr: &Attribute, name: &str) -> Result<u32> {
+    let value = &attr.meta.require_name_value()?.value;
+    match value {
+        Expr::Lit(ExprLit { lit: Lit::Int(lit), .. }) => lit.base10_parse(),
+        _ => Err(Error::new_spanned(value, format!("expected an integer, e.g. `#[{} = 1]`", name))),
+    }
+}
+
+/// Settings of a field given by its `#[location = N]`, `#[normalized]`, `#[integer]` and
+/// `#[double]` attributes.
+struct FieldAttributes {
+    location: Option<u32>,
+    normalized: bool,
+    kind: Option<(&'static str, Span)>,
+}
+
+fn field_attributes(attrs: &[Attribute]) -> Result<FieldAttributes> {
+    let mut settings = FieldAttributes { location: None, normalized: false, kind: None };
+    for attr in attrs {
+        if attr.path().is_ident("location") {
+            settings.location = Some(int_attribute(attr, "location")?);
+        } else if attr.path().is_ident("normalized") {
+            attr.meta.require_path_only()?;
+            settings.normalized = true;
+        } else if let Some(kind) = ["integer", "double"].iter().copied().find(|kind| attr.path().is_ident(*kind)) {
+            attr.meta.require_path_only()?;
+            if settings.kind.is_some() {
+                return Err(Error::new_spanned(attr, "a field is either `#[integer]` or `#[double]`"));
+            }
+            settings.kind = Some((kind, attr.span()));
+        }
+    }
+    if let (true, Some((kind, span))) = (settings.normalized, settings.kind) {
+        return Err(Error::new(span, format!("`#[{}]` fields cannot be `#[normalized]`", kind)));
+    }
+    Ok(settings)
+}
+
+pub fn derive(input: &DeriveInput) -> Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => return Err(Error::new_spanned(input, "Vertex can only be derived for structs")),
+    };
+    let mut divisor = 0;
+    for attr in &input.attrs {
+        if attr.path().is_ident("divisor") {
+            divisor = int_attribute(attr, "divisor")?;
+        }
+    }
+
+    // Matrices take several locations, so locations are only known once the field types are.
+    // They are computed in a constant, so that overlaps are reported at compile time.
+    let mut names = Vec::new();
+    let mut types = Vec::new();
+    let mut set_locations = Vec::new();
+    let mut attributes = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let settings = field_attributes(&field.attrs)?;
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        };
+        let ty = &field.ty;
+        let normalized = settings.normalized;
+        let kind = match settings.kind {
+            Some(("integer", _)) => quote!(Integer),
+            Some(("double", _)) => quote!(Double),
+            _ => quote!(Float),
+        };
+        names.push(field.ident.as_ref().map_or_else(|| format!("field {}", i), |ident| format!("`{}`", ident)));
+        types.push(ty);
+        set_locations.push(settings.location.map(|location| quote!(location = #location;)));
+        attributes.push(quote! {
+            let location = Self::__VERTEX_LOCATIONS[#i];
+            let locations = <#ty as ::learnopengl_rs::vao::VertexAttribute>::LOCATIONS;
+            for column in 0..locations {
+                attributes.push(::learnopengl_rs::vao::VertexAttribPointer {
+                    index: location + column,
+                    size: <#ty as ::learnopengl_rs::vao::VertexAttribute>::SIZE,
+                    ty: <#ty as ::learnopengl_rs::vao::VertexAttribute>::TYPE,
+                    normalized: #normalized as u8,
+                    stride,
+                    pointer: (::std::mem::offset_of!(Self, #member)
+                        + column as usize * ::std::mem::size_of::<#ty>() / locations as usize)
+                        as *const ::std::ffi::c_void,
+                    divisor: #divisor,
+                    kind: ::learnopengl_rs::vao::AttributeKind::#kind,
+                });
+            }
+        });
+    }
+
+    let mut overlaps = Vec::new();
+    for j in 0..fields.len() {
+        for i in 0..j {
+            let message = format!("the locations of {} overlap those of {}", names[j], names[i]);
+            overlaps.push(quote! {
+                if starts[#j] < starts[#i] + counts[#i] && starts[#i] < starts[#j] + counts[#j] {
+                    panic!(#message);
+                }
+            });
+        }
+    }
+    let indices = 0..fields.len();
+    let len = fields.len();
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    // Constants of generic types are only evaluated once instantiated, by `attributes`
+    let check = if input.generics.params.is_empty() {
+        Some(quote!(const _: [u32; #len] = #name::__VERTEX_LOCATIONS;))
+    } else {
+        None
+    };
+    Ok(quote! {
+        #check
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// First location of each field.
+            #[doc(hidden)]
+            #[allow(unused_assignments, unused_mut)]
+            const __VERTEX_LOCATIONS: [u32; #len] = {
+                let counts: [u32; #len] = [#(<#types as ::learnopengl_rs::vao::VertexAttribute>::LOCATIONS),*];
+                let mut starts = [0; #len];
+                // Fields without a location follow the previous one, like enum discriminants
+                let mut location = 0;
+                #(
+                    #set_locations
+                    starts[#indices] = location;
+                    location += counts[#indices];
+                )*
+                #(#overlaps)*
+                starts
+            };
+        }
+
+        impl #impl_generics ::learnopengl_rs::vao::Vertex for #name #ty_generics #where_clause {
+            fn attributes() -> ::std::vec::Vec<::learnopengl_rs::vao::VertexAttribPointer> {
+                let stride = ::std::mem::size_of::<Self>() as i32;
+                let mut attributes = ::std::vec::Vec::new();
+                #(#attributes)*
+                attributes
+            }
+        }
+    })
+}
@@ -6,15 +6,15 @@ use learnopengl_rs::{OpenGLApp, shaders};
 use learnopengl_rs::glutin::run_in_window;
 use learnopengl_rs::shaders::ShaderProgram;
 use learnopengl_rs::vao;
-use learnopengl_rs::vao::{VertexArrayObject, VertexAttribPointer};
+use learnopengl_rs::vao::{Vertex as _, VertexArrayObject};
 
-struct HelloTriangle {
+pub struct HelloTriangle {
     vao: VertexArrayObject,
     prgm: ShaderProgram,
 }
 
 impl HelloTriangle {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             vao: VertexArrayObject::default(),
             prgm: ShaderProgram::default(),
@@ -22,6 +22,7 @@ impl HelloTriangle {
     }
 }
 
+#[derive(vao::Vertex)]
 #[repr(C)]
 struct Vertex {
     position: [GLfloat; 3],
@@ -39,7 +40,7 @@ impl OpenGLApp for HelloTriangle {
             Vertex { position: [0.5, -0.5, 0.0] },
             Vertex { position: [0.0, 0.5, 0.5] }
         ];
-        self.vao = vao::create(&vertices, &[VertexAttribPointer::default()]);
+        self.vao = vao::create(&vertices, &Vertex::attributes());
 
         // Create GLSL shaders
         let vs_src = include_str!("../res/shaders/hello_triangle.vs");
@@ -49,13 +50,12 @@ impl OpenGLApp for HelloTriangle {
         self.prgm = shaders::link(&vs, &fs).unwrap();
     }
 
-    fn render(&self) {
+    fn render(&self, _alpha: f32) {
         unsafe {
             gl::ClearColor(0.6, 0.6, 0.6, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
             gl::UseProgram(self.prgm.id);
-            gl::BindVertexArray(self.vao.id);
-            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            self.vao.draw();
         }
     }
 }
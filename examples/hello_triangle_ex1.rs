@@ -6,15 +6,15 @@ use gl::{self, types::*};
 use learnopengl_rs::{OpenGLApp, shaders, vao};
 use learnopengl_rs::glutin::run_in_window;
 use learnopengl_rs::shaders::ShaderProgram;
-use learnopengl_rs::vao::{VertexArrayObject, VertexAttribPointer};
+use learnopengl_rs::vao::{Vertex as _, VertexArrayObject};
 
-struct HelloTriangleEx1 {
+pub struct HelloTriangleEx1 {
     vao: VertexArrayObject,
     prgm: ShaderProgram,
 }
 
 impl HelloTriangleEx1 {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             vao: VertexArrayObject::default(),
             prgm: ShaderProgram::default(),
@@ -22,6 +22,7 @@ impl HelloTriangleEx1 {
     }
 }
 
+#[derive(vao::Vertex)]
 #[repr(C)]
 struct Vertex {
     position: [GLfloat; 3],
@@ -43,7 +44,7 @@ impl OpenGLApp for HelloTriangleEx1 {
             Vertex { position: [1.0, 0.5, 0.0] },
         ];
 
-        self.vao = vao::create(&vertices, &[VertexAttribPointer::default()]);
+        self.vao = vao::create(&vertices, &Vertex::attributes());
 
         // Create GLSL shaders
         let vs_src: &'static str = include_str!("../res/shaders/hello_triangle.vs");
@@ -53,13 +54,12 @@ impl OpenGLApp for HelloTriangleEx1 {
         self.prgm = shaders::link(&vs, &fs).unwrap();
     }
 
-    fn render(&self) {
+    fn render(&self, _alpha: f32) {
         unsafe {
             gl::ClearColor(0.6, 0.6, 0.6, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
             gl::UseProgram(self.prgm.id);
-            gl::BindVertexArray(self.vao.id);
-            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            self.vao.draw();
         }
     }
 }
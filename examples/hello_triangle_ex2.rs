@@ -7,16 +7,16 @@ use gl::{self, types::*};
 use learnopengl_rs::{OpenGLApp, shaders, vao};
 use learnopengl_rs::glutin::run_in_window;
 use learnopengl_rs::shaders::ShaderProgram;
-use learnopengl_rs::vao::{VertexArrayObject, VertexAttribPointer};
+use learnopengl_rs::vao::{Vertex as _, VertexArrayObject};
 
-struct HelloTriangleEx2 {
+pub struct HelloTriangleEx2 {
     vao_a: VertexArrayObject,
     vao_b: VertexArrayObject,
     prgm: ShaderProgram,
 }
 
 impl HelloTriangleEx2 {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             vao_a: VertexArrayObject::default(),
             vao_b: VertexArrayObject::default(),
@@ -25,6 +25,7 @@ impl HelloTriangleEx2 {
     }
 }
 
+#[derive(vao::Vertex)]
 #[repr(C)]
 struct Vertex {
     position: [GLfloat; 3],
@@ -42,14 +43,14 @@ impl OpenGLApp for HelloTriangleEx2 {
             Vertex { position: [0.4, -0.5, 0.0] },
             Vertex { position: [-0.1, 0.5, 0.0] },
         ];
-        self.vao_a = vao::create(&vertices_a, &[VertexAttribPointer::default()]);
+        self.vao_a = vao::create(&vertices_a, &Vertex::attributes());
 
         let vertices_b: [Vertex; 3] = [
             Vertex { position: [0.1, 0.5, 0.0] },
             Vertex { position: [0.6, -0.5, 0.0] },
             Vertex { position: [1.0, 0.5, 0.0] },
         ];
-        self.vao_b = vao::create(&vertices_b, &[VertexAttribPointer::default()]);
+        self.vao_b = vao::create(&vertices_b, &Vertex::attributes());
 
         // Create GLSL shaders
         let vs_src: &'static str = include_str!("../res/shaders/hello_triangle.vs");
@@ -59,15 +60,13 @@ impl OpenGLApp for HelloTriangleEx2 {
         self.prgm = shaders::link(&vs, &fs).unwrap();
     }
 
-    fn render(&self) {
+    fn render(&self, _alpha: f32) {
         unsafe {
             gl::ClearColor(0.6, 0.6, 0.6, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
             gl::UseProgram(self.prgm.id);
-            gl::BindVertexArray(self.vao_a.id);
-            gl::DrawArrays(gl::TRIANGLES, 0, 3);
-            gl::BindVertexArray(self.vao_b.id);
-            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            self.vao_a.draw();
+            self.vao_b.draw();
         }
     }
 }
@@ -5,9 +5,10 @@ use gl::{self, types::*};
 use learnopengl_rs::{OpenGLApp, shaders, vao};
 use learnopengl_rs::glutin::run_in_window;
 use learnopengl_rs::shaders::ShaderProgram;
-use learnopengl_rs::vao::{VertexArrayObject, VertexAttribPointer};
+use learnopengl_rs::shaders::preprocessor::Preprocessor;
+use learnopengl_rs::vao::{Vertex as _, VertexArrayObject};
 
-struct HelloTriangleEx3 {
+pub struct HelloTriangleEx3 {
     vao_a: VertexArrayObject,
     vao_b: VertexArrayObject,
     prgm_a: ShaderProgram,
@@ -15,7 +16,7 @@ struct HelloTriangleEx3 {
 }
 
 impl HelloTriangleEx3 {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             vao_a: VertexArrayObject::default(),
             vao_b: VertexArrayObject::default(),
@@ -25,6 +26,7 @@ impl HelloTriangleEx3 {
     }
 }
 
+#[derive(vao::Vertex)]
 #[repr(C)]
 struct Vertex {
     position: [GLfloat; 3],
@@ -42,40 +44,44 @@ impl OpenGLApp for HelloTriangleEx3 {
             Vertex { position: [0.4, -0.5, 0.0] },
             Vertex { position: [-0.1, 0.5, 0.0] },
         ];
-        self.vao_a = vao::create(&vertices_a, &[VertexAttribPointer::default()]);
+        self.vao_a = vao::create(&vertices_a, &Vertex::attributes());
 
         let vertices_b: [Vertex; 3] = [
             Vertex { position: [0.1, 0.5, 0.0] },
             Vertex { position: [0.6, -0.5, 0.0] },
             Vertex { position: [1.0, 0.5, 0.0] },
         ];
-        self.vao_b = vao::create(&vertices_b, &[VertexAttribPointer::default()]);
+        self.vao_b = vao::create(&vertices_b, &Vertex::attributes());
 
         // Create GLSL shaders
         let vs_src = include_str!("../res/shaders/hello_triangle.vs");
         let vs = shaders::compile(vs_src, gl::VERTEX_SHADER).unwrap();
 
-        let fs_template = include_str!("../res/shaders/hello_triangle_ex3.fs.template");
+        // Both fragment shaders are built from the same source with a different COLOR
+        let with_color = |color: &str| Preprocessor {
+            defines: vec![("COLOR".to_owned(), color.to_owned())],
+            ..Preprocessor::default()
+        };
 
-        let orange_fs_src = &fs_template.replace("{color}", "1.0, 0.6, 0.2, 1.0");
-        let orange_fs = shaders::compile(orange_fs_src, gl::FRAGMENT_SHADER).unwrap();
+        let orange_fs = with_color("vec4(1.0, 0.6, 0.2, 1.0)")
+            .compile_file("hello_triangle_ex3.fs", gl::FRAGMENT_SHADER)
+            .unwrap();
         self.prgm_a = shaders::link(&vs, &orange_fs).unwrap();
 
-        let pink_fs_src = &fs_template.replace("{color}", "1.0, 0.75, 0.8, 1.0");
-        let pink_fs = shaders::compile(pink_fs_src, gl::FRAGMENT_SHADER).unwrap();
+        let pink_fs = with_color("vec4(1.0, 0.75, 0.8, 1.0)")
+            .compile_file("hello_triangle_ex3.fs", gl::FRAGMENT_SHADER)
+            .unwrap();
         self.prgm_b = shaders::link(&vs, &pink_fs).unwrap();
     }
 
-    fn render(&self) {
+    fn render(&self, _alpha: f32) {
         unsafe {
             gl::ClearColor(0.6, 0.6, 0.6, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
             gl::UseProgram(self.prgm_a.id);
-            gl::BindVertexArray(self.vao_a.id);
-            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            self.vao_a.draw();
             gl::UseProgram(self.prgm_b.id);
-            gl::BindVertexArray(self.vao_b.id);
-            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            self.vao_b.draw();
         }
     }
 }
@@ -4,15 +4,15 @@ use learnopengl_rs::{OpenGLApp, shaders};
 use learnopengl_rs::glutin::run_in_window;
 use learnopengl_rs::shaders::ShaderProgram;
 use learnopengl_rs::vao;
-use learnopengl_rs::vao::{VertexArrayObject, VertexAttribPointer};
+use learnopengl_rs::vao::{Vertex as _, VertexArrayObject};
 
-struct HelloTriangleIndexed {
+pub struct HelloTriangleIndexed {
     vao: VertexArrayObject,
     prgm: ShaderProgram,
 }
 
 impl HelloTriangleIndexed {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             vao: VertexArrayObject::default(),
             prgm: ShaderProgram::default(),
@@ -20,6 +20,7 @@ impl HelloTriangleIndexed {
     }
 }
 
+#[derive(vao::Vertex)]
 #[repr(C)]
 struct Vertex {
     position: [GLfloat; 3],
@@ -39,7 +40,7 @@ impl OpenGLApp for HelloTriangleIndexed {
             Vertex { position: [-0.5, 0.5, 0.0] },
         ];
         let indices: [GLuint; 6] = [0, 1, 3, 1, 2, 3];
-        self.vao = vao::create_indexed(&vertices, &[VertexAttribPointer::default()], &indices);
+        self.vao = vao::create_indexed(&vertices, &Vertex::attributes(), &indices);
 
         // Create GLSL shaders
         let vs_src = include_str!("../res/shaders/hello_triangle.vs");
@@ -49,13 +50,12 @@ impl OpenGLApp for HelloTriangleIndexed {
         self.prgm = shaders::link(&vs, &fs).unwrap();
     }
 
-    fn render(&self) {
+    fn render(&self, _alpha: f32) {
         unsafe {
             gl::ClearColor(0.6, 0.6, 0.6, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
             gl::UseProgram(self.prgm.id);
-            gl::BindVertexArray(self.vao.id);
-            gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, std::ptr::null());
+            self.vao.draw();
         }
     }
 }
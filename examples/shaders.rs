@@ -1,19 +1,17 @@
-use std::ffi::c_void;
-
 use gl::{self, types::*};
 
 use learnopengl_rs::{OpenGLApp, shaders, vao};
 use learnopengl_rs::glutin::run_in_window;
 use learnopengl_rs::shaders::ShaderProgram;
-use learnopengl_rs::vao::{VertexArrayObject, VertexAttribPointer};
+use learnopengl_rs::vao::{Vertex as _, VertexArrayObject};
 
-struct Shaders {
+pub struct Shaders {
     vao: VertexArrayObject,
     prgm: ShaderProgram,
 }
 
 impl Shaders {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             vao: VertexArrayObject::default(),
             prgm: ShaderProgram::default(),
@@ -21,6 +19,7 @@ impl Shaders {
     }
 }
 
+#[derive(vao::Vertex)]
 #[repr(C)]
 struct Vertex {
     position: [GLfloat; 3],
@@ -39,37 +38,19 @@ impl OpenGLApp for Shaders {
             Vertex { position: [0.0, 0.5, 0.0], color: [0.0, 0.0, 1.0] },
         ];
 
-        self.vao = vao::create(&vertices, &[
-            VertexAttribPointer {
-                index: 0,
-                size: 3,
-                ty: gl::FLOAT,
-                normalized: gl::FALSE as GLboolean,
-                stride: std::mem::size_of::<Vertex>() as GLsizei,
-                pointer: 0 as *const c_void,
-            },
-            VertexAttribPointer {
-                index: 1,
-                size: 3,
-                ty: gl::FLOAT,
-                normalized: gl::FALSE as GLboolean,
-                stride: std::mem::size_of::<Vertex>() as GLsizei,
-                pointer: std::mem::size_of::<[GLfloat; 3]>() as *const c_void,
-            }
-        ]);
+        self.vao = vao::create(&vertices, &Vertex::attributes());
 
         let vs = shaders::compile(include_str!("../res/shaders/shaders.vs"), gl::VERTEX_SHADER).unwrap();
         let fs = shaders::compile(include_str!("../res/shaders/shaders.fs"), gl::FRAGMENT_SHADER).unwrap();
         self.prgm = shaders::link(&vs, &fs).unwrap();
     }
 
-    fn render(&self) {
+    fn render(&self, _alpha: f32) {
         unsafe {
             gl::ClearColor(0.6, 0.6, 0.6, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
             gl::UseProgram(self.prgm.id);
-            gl::BindVertexArray(self.vao.id);
-            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            self.vao.draw();
         }
     }
 }
@@ -1,37 +1,36 @@
 use std::f32::consts::{FRAC_PI_4, PI};
-use std::ffi::{c_void, CString};
-use std::time::Instant;
 
 use gl::{self, types::*};
 use nalgebra::{Isometry3, Matrix4, Rotation3, Vector3};
 
-use learnopengl_rs::{OpenGLApp, shaders, textures};
+use learnopengl_rs::{OpenGLApp, shaders};
 use learnopengl_rs::glutin::run_in_window;
 use learnopengl_rs::shaders::ShaderProgram;
-use learnopengl_rs::textures::{Texture2d, Texture2dDescriptor, Texture2dParams};
+use learnopengl_rs::textures::{Texture2d, Texture2dOptions};
 use learnopengl_rs::vao;
-use learnopengl_rs::vao::{VertexArrayObject, VertexAttribPointer};
+use learnopengl_rs::vao::{Vertex as _, VertexArrayObject};
 
-struct Transformations {
+pub struct Transformations {
     vao: VertexArrayObject,
     texture1: Texture2d,
     texture2: Texture2d,
     prgm: ShaderProgram,
-    start_time: Instant,
+    elapsed: f32,
 }
 
 impl Transformations {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             vao: VertexArrayObject::default(),
             texture1: Texture2d::default(),
             texture2: Texture2d::default(),
             prgm: ShaderProgram::default(),
-            start_time: Instant::now(),
+            elapsed: 0.0,
         }
     }
 }
 
+#[derive(vao::Vertex)]
 #[repr(C)]
 struct Vertex {
     position: [GLfloat; 3],
@@ -51,56 +50,34 @@ impl OpenGLApp for Transformations {
             Vertex { position: [-0.5, 0.5, 0.0], tex: [0.0, 1.0] },
         ];
 
-        let indices = [
+        let indices: [u32; 6] = [
             0, 1, 3, 1, 2, 3
         ];
 
-        let stride = std::mem::size_of::<Vertex>() as GLsizei;
-        let position_offset = 0;
-        let tex_offset = position_offset + std::mem::size_of::<[GLfloat; 3]>();
+        self.vao = vao::create_indexed(&vertices, &Vertex::attributes(), &indices);
 
-        self.vao = vao::create_indexed(&vertices, &[
-            VertexAttribPointer {
-                index: 0,
-                size: 3,
-                ty: gl::FLOAT,
-                normalized: gl::FALSE as GLboolean,
-                stride: stride,
-                pointer: position_offset as *const c_void,
-            },
-            VertexAttribPointer {
-                index: 1,
-                size: 2,
-                ty: gl::FLOAT,
-                normalized: gl::FALSE as GLboolean,
-                stride: stride,
-                pointer: tex_offset as *const c_void,
-            }
-        ], &indices);
-
-        let image1 = image::open("res/textures/img.png").unwrap();
-        let data1 = image1.as_rgba8().unwrap();
-        self.texture1 = textures::create_2d(&Texture2dDescriptor {
+        self.texture1 = Texture2d::from_path("res/textures/img.png", &Texture2dOptions {
             unit: gl::TEXTURE0,
-            img: data1,
-            params: &Texture2dParams::default(),
-        });
+            ..Texture2dOptions::default()
+        }).unwrap();
 
-        let image2 = image::open("res/textures/awesomeface.png").unwrap().flipv();
-        let data2 = image2.as_rgba8().unwrap();
-        self.texture2 = textures::create_2d(&Texture2dDescriptor {
+        self.texture2 = Texture2d::from_path("res/textures/awesomeface.png", &Texture2dOptions {
             unit: gl::TEXTURE1,
-            img: data2,
-            params: &Texture2dParams::default(),
-        });
+            flip_vertically: true,
+            ..Texture2dOptions::default()
+        }).unwrap();
         let vs = shaders::compile(include_str!("../res/shaders/transformations.vs"), gl::VERTEX_SHADER).unwrap();
         let fs = shaders::compile(include_str!("../res/shaders/textures_multi.fs"), gl::FRAGMENT_SHADER).unwrap();
         self.prgm = shaders::link(&vs, &fs).unwrap();
     }
 
-    fn render(&self) {
+    fn update(&mut self, dt: f32) {
+        self.elapsed += dt;
+    }
+
+    fn render(&self, _alpha: f32) {
         unsafe {
-            let elapsed = self.start_time.elapsed().as_secs_f32();
+            let elapsed = self.elapsed;
             // let transform = Isometry3::new(Vector3::new(0.5, -0.5, 0.0), elapsed * FRAC_PI_4 * Vector3::z());
             let translate = Matrix4::new_translation(&Vector3::new(0.5, 0.0, 0.0));
             let rotate_center = Matrix4::new_rotation(elapsed * FRAC_PI_4 * Vector3::z());
@@ -109,19 +86,14 @@ impl OpenGLApp for Transformations {
             gl::ClearColor(0.6, 0.6, 0.6, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
             gl::UseProgram(self.prgm.id);
-            gl::Uniform1i(gl::GetUniformLocation(self.prgm.id, CString::new("texture1").unwrap().as_ptr()), 0);
-            gl::Uniform1i(gl::GetUniformLocation(self.prgm.id, CString::new("texture2").unwrap().as_ptr()), 1);
-            gl::UniformMatrix4fv(
-                gl::GetUniformLocation(self.prgm.id, CString::new("transform").unwrap().as_ptr()),
-                1,
-                gl::FALSE as GLboolean,
-                transform.as_ptr());
-            gl::BindVertexArray(self.vao.id);
+            self.prgm.set_uniform("texture1", &0).unwrap();
+            self.prgm.set_uniform("texture2", &1).unwrap();
+            self.prgm.set_uniform("transform", &transform).unwrap();
             gl::ActiveTexture(self.texture1.unit);
             gl::BindTexture(gl::TEXTURE_2D, self.texture1.id);
             gl::ActiveTexture(self.texture2.unit);
             gl::BindTexture(gl::TEXTURE_2D, self.texture2.id);
-            gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, 0 as *const c_void);
+            self.vao.draw();
         }
     }
 }
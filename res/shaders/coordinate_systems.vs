@@ -2,14 +2,17 @@
 
 layout (location = 0) in vec3 pos;
 layout (location = 1) in vec2 tex;
+// Per instance
+layout (location = 2) in mat4 model;
 
-uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+layout (std140) uniform Camera {
+    mat4 view;
+    mat4 projection;
+};
 
 out vec2 texCoord;
 
 void main() {
     gl_Position = projection * view * model * vec4(pos, 1.0);
     texCoord = tex;
-}
\ No newline at end of file
+}
@@ -0,0 +1,12 @@
+#version 330 core
+
+// The color is defined by the app, see examples/hello_triangle_ex3.rs
+#ifndef COLOR
+#define COLOR vec4(1.0, 0.5, 0.2, 1.0)
+#endif
+
+out vec4 FragColor;
+
+void main() {
+    FragColor = COLOR;
+}
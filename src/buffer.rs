@@ -0,0 +1,220 @@
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::mem::{size_of, size_of_val};
+
+use gl::{self, types::*};
+
+/// How often the content of a buffer is expected to change, a hint for the driver.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferUsage {
+    /// Set once and drawn many times.
+    Static,
+    /// Updated now and then and drawn many times.
+    Dynamic,
+    /// Updated before every draw, e.g. per-frame generated geometry.
+    Stream,
+}
+
+impl BufferUsage {
+    fn gl_usage(self) -> GLenum {
+        match self {
+            BufferUsage::Static => gl::STATIC_DRAW,
+            BufferUsage::Dynamic => gl::DYNAMIC_DRAW,
+            BufferUsage::Stream => gl::STREAM_DRAW,
+        }
+    }
+}
+
+/// A buffer object holding vertex or index data.
+///
+/// Updates go through the `GL_COPY_WRITE_BUFFER` binding point, so they leave the array and
+/// element array bindings, and thus the currently bound vertex array, untouched.
+pub struct Buffer {
+    pub id: GLuint,
+    /// Binding point the buffer is created for, e.g. `gl::ARRAY_BUFFER`.
+    pub target: GLenum,
+    usage: BufferUsage,
+    /// Size of the data store in bytes.
+    size: usize,
+}
+
+impl Default for Buffer {
+    fn default() -> Self {
+        Self { id: 0, target: gl::ARRAY_BUFFER, usage: BufferUsage::Static, size: 0 }
+    }
+}
+
+impl Buffer {
+    /// Create a buffer holding `data` and leave it bound to `target`.
+    ///
+    /// See [glBufferData](https://docs.gl/gl3/glBufferData) for details.
+    pub fn new<T>(target: GLenum, data: &[T], usage: BufferUsage) -> Self {
+        Self::allocate(target, size_of_val(data), data.as_ptr() as *const c_void, usage)
+    }
+
+    /// Create a buffer with room for `len` elements of type `T`, whose content is undefined
+    /// until it is written, and leave it bound to `target`.
+    pub fn with_capacity<T>(target: GLenum, len: usize, usage: BufferUsage) -> Self {
+        Self::allocate(target, len * size_of::<T>(), std::ptr::null(), usage)
+    }
+
+    fn allocate(target: GLenum, size: usize, data: *const c_void, usage: BufferUsage) -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(target, id);
+            gl::BufferData(target, size as GLsizeiptr, data, usage.gl_usage());
+        }
+        Self { id, target, usage, size }
+    }
+
+    /// Size of the data store in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of elements of type `T` the buffer can hold, 0 for zero-sized types.
+    pub fn len<T>(&self) -> usize {
+        self.size.checked_div(size_of::<T>()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn usage(&self) -> BufferUsage {
+        self.usage
+    }
+
+    /// Overwrite the elements starting at element `offset` with `data`.
+    /// The buffer is not resized, writing past its end is an error.
+    ///
+    /// See [glBufferSubData](https://docs.gl/gl3/glBufferSubData) for details.
+    pub fn update<T>(&self, offset: usize, data: &[T]) -> Result<(), String> {
+        let start = offset * size_of::<T>();
+        self.check_range(start, size_of_val(data))?;
+        unsafe {
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.id);
+            gl::BufferSubData(gl::COPY_WRITE_BUFFER, start as GLintptr, size_of_val(data) as GLsizeiptr,
+                              data.as_ptr() as *const c_void);
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+        }
+        Ok(())
+    }
+
+    /// Replace the content of the buffer with `data`, growing or shrinking it as needed.
+    ///
+    /// A new data store is allocated, so draws still reading the previous content do not
+    /// stall: the driver frees the old store, or orphans it, once they are done.
+    pub fn resize<T>(&mut self, data: &[T]) {
+        self.size = size_of_val(data);
+        self.respecify(data.as_ptr() as *const c_void);
+    }
+
+    /// Orphan the data store: allocate a new one of the same size, with undefined content.
+    ///
+    /// Call it before rewriting the whole buffer each frame, so that the writes do not wait for
+    /// the draws of the previous frame to be done reading it.
+    pub fn orphan(&self) {
+        self.respecify(std::ptr::null());
+    }
+
+    fn respecify(&self, data: *const c_void) {
+        unsafe {
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.id);
+            gl::BufferData(gl::COPY_WRITE_BUFFER, self.size as GLsizeiptr, data, self.usage.gl_usage());
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+        }
+    }
+
+    /// Map `len` elements starting at element `offset` for writing. Their previous content is
+    /// discarded, every element of the range is expected to be written.
+    /// The range is unmapped when the returned value is dropped.
+    ///
+    /// See [glMapBufferRange](https://docs.gl/gl3/glMapBufferRange) for details.
+    pub fn map_range<T: Copy>(&mut self, offset: usize, len: usize) -> Result<MappedRange<'_, T>, String> {
+        let start = offset * size_of::<T>();
+        let size = len * size_of::<T>();
+        self.check_range(start, size)?;
+        let ptr = unsafe {
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.id);
+            gl::MapBufferRange(gl::COPY_WRITE_BUFFER, start as GLintptr, size as GLsizeiptr,
+                               gl::MAP_WRITE_BIT | gl::MAP_INVALIDATE_RANGE_BIT)
+        };
+        if ptr.is_null() {
+            unsafe {
+                gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+            }
+            return Err(format!("Failed to map {} bytes at offset {} of buffer {}", size, start, self.id));
+        }
+        Ok(MappedRange { buffer: self, ptr: ptr as *mut T, len, marker: PhantomData })
+    }
+
+    fn check_range(&self, start: usize, size: usize) -> Result<(), String> {
+        if start + size > self.size {
+            return Err(format!("Range of {} bytes at offset {} is out of buffer {} of {} bytes",
+                               size, start, self.id, self.size));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        log::debug!("Dropping buffer {}", self.id);
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
+
+/// A range of a buffer mapped for writing, see `Buffer::map_range`.
+///
+/// The mapped memory is write-only, and the writes are only guaranteed to be visible to OpenGL
+/// once the range is unmapped, on drop.
+pub struct MappedRange<'a, T> {
+    buffer: &'a Buffer,
+    ptr: *mut T,
+    len: usize,
+    marker: PhantomData<&'a mut [T]>,
+}
+
+impl<T: Copy> MappedRange<'_, T> {
+    /// Number of elements in the range.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Write `value` at `index`, relative to the start of the range.
+    pub fn write(&mut self, index: usize, value: T) {
+        assert!(index < self.len, "index {} is out of a mapped range of {} elements", index, self.len);
+        // Mapped memory only has the alignment of the start of the buffer
+        unsafe { self.ptr.add(index).write_unaligned(value) }
+    }
+
+    /// Write `data` starting at `index`, relative to the start of the range.
+    pub fn write_slice(&mut self, index: usize, data: &[T]) {
+        assert!(index + data.len() <= self.len,
+                "{} elements at {} are out of a mapped range of {} elements", data.len(), index, self.len);
+        for (i, value) in data.iter().enumerate() {
+            unsafe { self.ptr.add(index + i).write_unaligned(*value) }
+        }
+    }
+}
+
+impl<T> Drop for MappedRange<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.buffer.id);
+            if gl::UnmapBuffer(gl::COPY_WRITE_BUFFER) == gl::FALSE {
+                // The data store was lost, e.g. on a display mode change
+                log::warn!("The content of buffer {} was corrupted while mapped", self.buffer.id);
+            }
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+        }
+    }
+}
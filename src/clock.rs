@@ -0,0 +1,287 @@
+use std::time::Instant;
+
+/// Source of the simulation time fed to `OpenGLApp::update` by the runners.
+///
+/// The runner ticks its clock once per frame. The clock starts at 0 on the first tick.
+/// Apps reach it through `OpenGLApp::control_clock` to pause, step or jump in time.
+///
+/// Clocks hold their pause, steps and jumps in `Controls`, which implement every method but
+/// `tick`.
+pub trait Clock {
+    /// Advance to the next frame and return the time elapsed since the previous tick, in seconds.
+    ///
+    /// The elapsed time is negative after jumping back in time with `set_time`. Runners do not
+    /// update the simulation on such ticks.
+    fn tick(&mut self) -> f32;
+
+    /// The pause, steps and jumps requested to the clock.
+    fn controls(&self) -> &Controls;
+
+    fn controls_mut(&mut self) -> &mut Controls;
+
+    /// Time of the last tick in seconds.
+    fn time(&self) -> f32 {
+        self.controls().time
+    }
+
+    /// Whether the clock follows the wall clock. Runners clamp long frames of real time clocks,
+    /// e.g. after a breakpoint, so that fixed time step simulations do not spiral.
+    fn is_realtime(&self) -> bool {
+        false
+    }
+
+    fn is_paused(&self) -> bool {
+        self.controls().paused
+    }
+
+    /// Stop the time. Ticks return 0 until `resume` is called, apart from `step` and `set_time`.
+    fn pause(&mut self) {
+        self.controls_mut().paused = true;
+    }
+
+    /// Restart the time from where it was paused.
+    fn resume(&mut self) {
+        self.controls_mut().paused = false;
+    }
+
+    /// Jump to `time` on the next tick. Jumping back in time yields a negative tick.
+    fn set_time(&mut self, time: f32) {
+        self.controls_mut().set_time(time);
+    }
+
+    /// Move forward by `dt` seconds on the next tick, on top of the time flowing, e.g. to step
+    /// through a paused animation frame by frame.
+    fn step(&mut self, dt: f32) {
+        self.controls_mut().step(dt);
+    }
+}
+
+/// Pause, steps and jumps requested to a clock, applied on its next tick.
+#[derive(Default)]
+pub struct Controls {
+    time: f32,
+    paused: bool,
+    target: Option<f32>,
+    step: f32,
+}
+
+impl Controls {
+    fn set_time(&mut self, time: f32) {
+        self.target = Some(time);
+        self.step = 0.0;
+    }
+
+    fn step(&mut self, dt: f32) {
+        match &mut self.target {
+            Some(target) => *target += dt,
+            None => self.step += dt,
+        }
+    }
+
+    /// Advance by `elapsed` seconds unless paused, along with the requested step or jump, and
+    /// return the time actually elapsed.
+    pub fn tick(&mut self, elapsed: f32) -> f32 {
+        let dt = match self.target.take() {
+            Some(target) => target - self.time,
+            None if self.paused => self.step,
+            None => elapsed + self.step,
+        };
+        self.step = 0.0;
+        self.time += dt;
+        dt
+    }
+}
+
+/// Follows the wall clock, and can be paused.
+pub struct RealTimeClock {
+    last_tick: Option<Instant>,
+    controls: Controls,
+}
+
+impl RealTimeClock {
+    pub fn new() -> Self {
+        Self {
+            last_tick: None,
+            controls: Controls::default(),
+        }
+    }
+}
+
+impl Default for RealTimeClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for RealTimeClock {
+    fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        let elapsed = self.last_tick.map_or(0.0, |last| (now - last).as_secs_f32());
+        self.last_tick = Some(now);
+        self.controls.tick(elapsed)
+    }
+
+    fn controls(&self) -> &Controls {
+        &self.controls
+    }
+
+    fn controls_mut(&mut self) -> &mut Controls {
+        &mut self.controls
+    }
+
+    fn is_realtime(&self) -> bool {
+        true
+    }
+}
+
+/// Advances by the same step on every tick whatever the time actually spent,
+/// e.g. to record a video at a given frame rate.
+pub struct FixedStepClock {
+    step: f32,
+    started: bool,
+    controls: Controls,
+}
+
+impl FixedStepClock {
+    /// Create a clock advancing by `step` seconds per tick.
+    pub fn new(step: f32) -> Self {
+        Self {
+            step,
+            started: false,
+            controls: Controls::default(),
+        }
+    }
+
+    /// Create a clock advancing by one frame at `fps` frames per second per tick.
+    pub fn with_fps(fps: f32) -> Self {
+        Self::new(1.0 / fps)
+    }
+}
+
+impl Clock for FixedStepClock {
+    fn tick(&mut self) -> f32 {
+        let elapsed = if self.started { self.step } else { 0.0 };
+        self.started = true;
+        self.controls.tick(elapsed)
+    }
+
+    fn controls(&self) -> &Controls {
+        &self.controls
+    }
+
+    fn controls_mut(&mut self) -> &mut Controls {
+        &mut self.controls
+    }
+}
+
+/// Only moves when told to, e.g. to render a scene at an exact time or step through an animation.
+///
+/// The time set with `set_time` or `advance` is reported by the next tick. Pausing it has no
+/// effect since the time does not flow by itself.
+#[derive(Default)]
+pub struct ManualClock {
+    controls: Controls,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a clock that jumps to `time` on its first tick.
+    pub fn at(time: f32) -> Self {
+        let mut clock = Self::new();
+        clock.controls.set_time(time);
+        clock
+    }
+
+    /// Move forward by `dt` seconds on the next tick, same as `step`.
+    pub fn advance(&mut self, dt: f32) {
+        self.controls.step(dt);
+    }
+}
+
+impl Clock for ManualClock {
+    fn tick(&mut self) -> f32 {
+        self.controls.tick(0.0)
+    }
+
+    fn controls(&self) -> &Controls {
+        &self.controls
+    }
+
+    fn controls_mut(&mut self) -> &mut Controls {
+        &mut self.controls
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticks(clock: &mut impl Clock, n: usize) -> Vec<f32> {
+        (0..n).map(|_| clock.tick()).collect()
+    }
+
+    #[test]
+    fn fixed_step_clock() {
+        let mut clock = FixedStepClock::new(0.25);
+        assert_eq!(ticks(&mut clock, 3), [0.0, 0.25, 0.25]);
+        assert_eq!(clock.time(), 0.5);
+
+        clock.pause();
+        assert!(clock.is_paused());
+        assert_eq!(ticks(&mut clock, 2), [0.0, 0.0]);
+        clock.step(0.125);
+        assert_eq!(ticks(&mut clock, 2), [0.125, 0.0]);
+        clock.resume();
+        assert_eq!(clock.tick(), 0.25);
+        assert_eq!(clock.time(), 0.875);
+
+        clock.set_time(4.0);
+        assert_eq!(clock.tick(), 3.125);
+        assert_eq!(clock.tick(), 0.25);
+        assert_eq!(clock.time(), 4.25);
+        assert_eq!(ticks(&mut FixedStepClock::with_fps(4.0), 2), [0.0, 0.25]);
+    }
+
+    #[test]
+    fn manual_clock() {
+        let mut clock = ManualClock::at(2.5);
+        assert_eq!(ticks(&mut clock, 2), [2.5, 0.0]);
+        assert_eq!(clock.time(), 2.5);
+
+        clock.advance(0.5);
+        clock.step(0.25);
+        assert_eq!(ticks(&mut clock, 2), [0.75, 0.0]);
+        assert_eq!(clock.time(), 3.25);
+
+        // Steps after a jump move from the new time
+        clock.set_time(1.0);
+        clock.step(0.5);
+        assert_eq!(clock.tick(), -1.75);
+        assert_eq!(clock.time(), 1.5);
+        assert!(!clock.is_realtime());
+    }
+
+    #[test]
+    fn rewind() {
+        let mut clock = FixedStepClock::new(0.25);
+        assert_eq!(ticks(&mut clock, 5), [0.0, 0.25, 0.25, 0.25, 0.25]);
+        clock.set_time(0.25);
+        assert_eq!(clock.tick(), -0.75);
+        assert_eq!(clock.time(), 0.25);
+        assert_eq!(clock.tick(), 0.25);
+    }
+
+    #[test]
+    fn paused_real_time_clock() {
+        let mut clock = RealTimeClock::new();
+        clock.pause();
+        clock.tick();
+        assert_eq!(clock.tick(), 0.0);
+        clock.step(0.5);
+        assert_eq!(clock.tick(), 0.5);
+        assert!(clock.is_realtime());
+    }
+}
@@ -0,0 +1,67 @@
+use std::ffi::CStr;
+
+use gl::{self, types::*};
+
+/// OpenGL profile of a context.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    Core,
+    Compatibility,
+}
+
+/// Settings of the OpenGL context and default framebuffer created by the runners.
+#[derive(Clone, Debug)]
+pub struct ContextConfig {
+    /// OpenGL versions to try, in order of preference.
+    /// The first one the driver accepts is used.
+    pub versions: Vec<(u8, u8)>,
+    pub profile: Profile,
+    /// Request a debug context and forward its debug messages to the `log` crate,
+    /// see `debug::enable`.
+    pub debug: bool,
+    /// Number of samples per pixel for multisample anti-aliasing, 0 to disable it.
+    pub msaa_samples: u16,
+    /// Request an sRGB capable framebuffer and enable `GL_FRAMEBUFFER_SRGB` so that
+    /// linear colors written by shaders are gamma corrected.
+    pub srgb: bool,
+    /// Depth buffer bits, 0 for no depth buffer.
+    pub depth_bits: u8,
+    /// Stencil buffer bits, 0 for no stencil buffer.
+    pub stencil_bits: u8,
+    /// Synchronize buffer swaps with the display refresh rate.
+    pub vsync: bool,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            versions: vec![(3, 3)],
+            profile: Profile::Core,
+            debug: false,
+            msaa_samples: 0,
+            srgb: false,
+            depth_bits: 24,
+            stencil_bits: 8,
+            vsync: true,
+        }
+    }
+}
+
+/// Whether the current context has at least the given OpenGL version or supports `extension`,
+/// e.g. `has_feature((4, 3), "GL_KHR_debug")`.
+pub fn has_feature(version: (GLint, GLint), extension: &str) -> bool {
+    unsafe {
+        let (mut major, mut minor) = (0, 0);
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+        if (major, minor) >= version {
+            return true;
+        }
+        let mut count = 0;
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+        (0..count as GLuint).any(|i| {
+            let ext = gl::GetStringi(gl::EXTENSIONS, i);
+            !ext.is_null() && CStr::from_ptr(ext as *const _).to_bytes() == extension.as_bytes()
+        })
+    }
+}
@@ -0,0 +1,161 @@
+use std::backtrace::Backtrace;
+use std::ffi::{c_void, CStr};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use gl::{self, types::*};
+use log::Level;
+
+use crate::context;
+
+/// Severity of a debug message, from least to most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Notification,
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    fn from_gl(severity: GLenum) -> Self {
+        match severity {
+            gl::DEBUG_SEVERITY_HIGH => Severity::High,
+            gl::DEBUG_SEVERITY_MEDIUM => Severity::Medium,
+            gl::DEBUG_SEVERITY_LOW => Severity::Low,
+            _ => Severity::Notification,
+        }
+    }
+
+    fn level(self) -> Level {
+        match self {
+            Severity::High => Level::Error,
+            Severity::Medium => Level::Warn,
+            Severity::Low => Level::Info,
+            Severity::Notification => Level::Debug,
+        }
+    }
+}
+
+pub struct DebugConfig {
+    /// Messages less severe than this are ignored.
+    pub min_severity: Severity,
+    /// Abort the process when the driver reports an error, printing a backtrace of the offending
+    /// GL call. Panicking instead would abort anyway, as panics cannot unwind through the driver.
+    pub abort_on_error: bool,
+}
+
+impl Default for DebugConfig {
+    fn default() -> Self {
+        Self {
+            min_severity: Severity::Low,
+            abort_on_error: false,
+        }
+    }
+}
+
+static MIN_SEVERITY: AtomicU8 = AtomicU8::new(Severity::Low as u8);
+static ABORT_ON_ERROR: AtomicBool = AtomicBool::new(false);
+
+/// Whether the current context supports debug output, either as core OpenGL 4.3
+/// or through the `GL_KHR_debug` extension.
+pub fn is_supported() -> bool {
+    gl::DebugMessageCallback::is_loaded() && context::has_feature((4, 3), "GL_KHR_debug")
+}
+
+/// Forward the debug messages of the current context to the `log` crate, under the `gl` target.
+///
+/// Messages are reported synchronously, on the thread making the offending call.
+/// Returns `false`, leaving the context untouched, if debug output is not supported.
+/// Most drivers only report messages, errors aside, for contexts created with the debug flag
+/// (see `ContextConfig::debug`).
+pub fn enable(config: &DebugConfig) -> bool {
+    if !is_supported() {
+        log::warn!(target: "gl", "Debug output is not supported by this context");
+        return false;
+    }
+    MIN_SEVERITY.store(config.min_severity as u8, Ordering::Relaxed);
+    ABORT_ON_ERROR.store(config.abort_on_error, Ordering::Relaxed);
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(Some(callback), std::ptr::null());
+        gl::DebugMessageControl(gl::DONT_CARE, gl::DONT_CARE, gl::DONT_CARE, 0, std::ptr::null(), gl::TRUE);
+    }
+    true
+}
+
+/// Stop forwarding debug messages.
+pub fn disable() {
+    if gl::DebugMessageCallback::is_loaded() {
+        unsafe {
+            gl::DebugMessageCallback(None, std::ptr::null());
+            gl::Disable(gl::DEBUG_OUTPUT);
+        }
+    }
+}
+
+fn source_name(source: GLenum) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API => "api",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    }
+}
+
+fn type_name(ty: GLenum) -> &'static str {
+    match ty {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        gl::DEBUG_TYPE_PUSH_GROUP => "push group",
+        gl::DEBUG_TYPE_POP_GROUP => "pop group",
+        _ => "other",
+    }
+}
+
+extern "system" fn callback(
+    source: GLenum,
+    ty: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    length: GLsizei,
+    message: *const GLchar,
+    _user_param: *mut c_void,
+) {
+    let severity = Severity::from_gl(severity);
+    let is_error = ty == gl::DEBUG_TYPE_ERROR;
+    if !is_error && (severity as u8) < MIN_SEVERITY.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let message = if message.is_null() {
+        String::new()
+    } else if length >= 0 {
+        let bytes = unsafe { std::slice::from_raw_parts(message as *const u8, length as usize) };
+        String::from_utf8_lossy(bytes).into_owned()
+    } else {
+        unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned()
+    };
+    let level = if is_error { Level::Error } else { severity.level() };
+    log::log!(
+        target: "gl",
+        level,
+        "[{} {}] {:?} #{}: {}",
+        source_name(source),
+        type_name(ty),
+        severity,
+        id,
+        message.trim_end()
+    );
+
+    if is_error && ABORT_ON_ERROR.load(Ordering::Relaxed) {
+        eprintln!("OpenGL error #{}: {}\n{}", id, message.trim_end(), Backtrace::force_capture());
+        std::process::abort();
+    }
+}
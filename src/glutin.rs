@@ -1,27 +1,64 @@
 use gl::{self};
 use glutin;
 use glutin::{
-    Api, ContextBuilder, dpi::LogicalSize, event_loop::EventLoop, GlProfile, GlRequest,
-    window::WindowBuilder,
+    Api, ContextBuilder, dpi::LogicalSize, event_loop::EventLoop, GlProfile, GlRequest, NotCurrent,
+    window::WindowBuilder, WindowedContext,
 };
-use glutin::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
+use glutin::event::{ElementState, Event, WindowEvent};
 use glutin::event_loop::ControlFlow;
+pub use glutin::event::{ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode};
 
-use crate::OpenGLApp;
+use crate::{OpenGLApp, Simulation};
+use crate::clock::{Clock, RealTimeClock};
+use crate::context::{ContextConfig, Profile};
+use crate::debug::{self, DebugConfig};
 
-pub fn run_in_window<T: 'static + OpenGLApp>(mut app: T) {
+/// Create a windowed context for the first OpenGL version in `config.versions`
+/// supported by the driver.
+fn build_windowed_context(
+    config: &ContextConfig,
+    window_builder: WindowBuilder,
+    event_loop: &EventLoop<()>,
+) -> Result<WindowedContext<NotCurrent>, String> {
+    let profile = match config.profile {
+        Profile::Core => GlProfile::Core,
+        Profile::Compatibility => GlProfile::Compatibility,
+    };
+    let mut errors = Vec::new();
+    for &(major, minor) in &config.versions {
+        let context = ContextBuilder::new()
+            .with_gl(GlRequest::Specific(Api::OpenGl, (major, minor)))
+            .with_gl_profile(profile)
+            .with_gl_debug_flag(config.debug)
+            .with_multisampling(config.msaa_samples)
+            .with_srgb(config.srgb)
+            .with_depth_buffer(config.depth_bits)
+            .with_stencil_buffer(config.stencil_bits)
+            .with_vsync(config.vsync)
+            .build_windowed(window_builder.clone(), event_loop);
+        match context {
+            Ok(context) => return Ok(context),
+            Err(e) => errors.push(format!("OpenGL {}.{}: {}", major, minor, e)),
+        }
+    }
+    Err(format!("Failed to create an OpenGL context ({})", errors.join(", ")))
+}
+
+/// Run the app in a window, following the wall clock.
+pub fn run_in_window<T: 'static + OpenGLApp>(app: T) {
+    run_in_window_with_clock(app, RealTimeClock::new())
+}
+
+/// Run the app in a window, advancing its simulation by the given clock on each frame.
+pub fn run_in_window_with_clock<T: 'static + OpenGLApp, C: 'static + Clock>(mut app: T, mut clock: C) {
     let event_loop = EventLoop::new();
     let window_builder = WindowBuilder::new()
         .with_title(app.title())
         .with_inner_size(LogicalSize::new(app.width(), app.height()))
         .with_resizable(app.is_resizable());
 
-    let windowed_context = ContextBuilder::new()
-        .with_vsync(true)
-        .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
-        .with_gl_profile(GlProfile::Core)
-        .build_windowed(window_builder, &event_loop)
-        .unwrap();
+    let config = app.context_config();
+    let windowed_context = build_windowed_context(&config, window_builder, &event_loop).unwrap();
 
     // It is essential to make the context current before calling `gl::load_with`.
     let gl_window = unsafe { windowed_context.make_current() }.unwrap();
@@ -29,8 +66,20 @@ pub fn run_in_window<T: 'static + OpenGLApp>(mut app: T) {
     // Load the OpenGL function pointers
     gl::load_with(|symbol| gl_window.get_proc_address(symbol));
 
+    if config.srgb {
+        unsafe {
+            gl::Enable(gl::FRAMEBUFFER_SRGB);
+        }
+    }
+
+    if config.debug {
+        debug::enable(&DebugConfig::default());
+    }
+
     app.initialize();
 
+    let mut modifiers = ModifiersState::empty();
+    let mut simulation = Simulation::default();
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll; // continuously run the loop even with no events dispatched
         match event {
@@ -43,16 +92,32 @@ pub fn run_in_window<T: 'static + OpenGLApp>(mut app: T) {
                     app.resize(sz.width, sz.height);
                 }
                 WindowEvent::KeyboardInput { input, .. } => {
-                    if let Some(VirtualKeyCode::Escape) = input.virtual_keycode {
-                        if input.state == ElementState::Pressed {
+                    if let Some(key) = input.virtual_keycode {
+                        match input.state {
+                            ElementState::Pressed => app.key_pressed(key, modifiers),
+                            ElementState::Released => app.key_released(key, modifiers),
+                        }
+                        if key == VirtualKeyCode::Escape && input.state == ElementState::Pressed {
                             *control_flow = ControlFlow::Exit
                         }
                     }
                 }
+                WindowEvent::ModifiersChanged(state) => modifiers = state,
+                WindowEvent::CursorMoved { position, .. } => {
+                    app.mouse_moved(position.x, position.y, modifiers);
+                }
+                WindowEvent::MouseInput { state, button, .. } => match state {
+                    ElementState::Pressed => app.mouse_pressed(button, modifiers),
+                    ElementState::Released => app.mouse_released(button, modifiers),
+                },
+                WindowEvent::MouseWheel { delta, .. } => app.scrolled(delta, modifiers),
+                WindowEvent::Focused(focused) => app.focused(focused),
+                WindowEvent::ReceivedCharacter(c) => app.text_input(c, modifiers),
                 _ => (),
             },
             Event::MainEventsCleared => {
-                app.render();
+                let alpha = simulation.frame(&mut app, &mut clock);
+                app.render(alpha);
                 app.render_ui();
                 gl_window.swap_buffers().unwrap();
             }
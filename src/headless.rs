@@ -0,0 +1,323 @@
+use std::ffi::c_void;
+use std::sync::Mutex;
+
+use gl::{self, types::*};
+use image::RgbaImage;
+use khronos_egl as egl;
+
+use crate::{OpenGLApp, Simulation};
+use crate::clock::{Clock, FixedStepClock};
+use crate::context::{ContextConfig, Profile};
+use crate::debug::{self, DebugConfig};
+
+/// `EGL_PLATFORM_SURFACELESS_MESA` from the `EGL_MESA_platform_surfaceless` extension.
+const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+type Egl = egl::DynamicInstance<egl::EGL1_4>;
+
+/// The OpenGL function pointers are global, so apps are run one at a time, e.g. by tests
+/// running on parallel threads.
+static RUN_LOCK: Mutex<()> = Mutex::new(());
+
+/// An offscreen OpenGL context.
+///
+/// The context has no default framebuffer, so frames are rendered into a framebuffer object
+/// that stays bound as the draw framebuffer. With multisampling, frames are resolved into a
+/// second framebuffer object before being read back.
+struct HeadlessContext {
+    egl: Egl,
+    display: egl::Display,
+    context: egl::Context,
+    width: GLsizei,
+    height: GLsizei,
+    fbo: GLuint,
+    color_rbo: GLuint,
+    depth_stencil_rbo: GLuint,
+    resolve_fbo: GLuint,
+    resolve_rbo: GLuint,
+}
+
+impl HeadlessContext {
+    fn new(width: u32, height: u32, config: &ContextConfig) -> Result<Self, String> {
+        let egl = unsafe { Egl::load_required() }
+            .map_err(|e| format!("Failed to load libEGL: {}", e))?;
+        let display = Self::open_display(&egl)?;
+        egl.initialize(display)
+            .map_err(|e| format!("Failed to initialize EGL display: {}", e))?;
+
+        let context = Self::create_context(&egl, display, config).inspect_err(|_| {
+            let _ = egl.terminate(display);
+        })?;
+
+        let mut ctx = Self {
+            egl,
+            display,
+            context,
+            width: width as GLsizei,
+            height: height as GLsizei,
+            fbo: 0,
+            color_rbo: 0,
+            depth_stencil_rbo: 0,
+            resolve_fbo: 0,
+            resolve_rbo: 0,
+        };
+        ctx.create_framebuffers(config)?;
+        Ok(ctx)
+    }
+
+    /// Open the surfaceless Mesa platform when available so that no display server is needed,
+    /// otherwise fall back to the default display.
+    fn open_display(egl: &Egl) -> Result<egl::Display, String> {
+        let client_extensions = egl
+            .query_string(None, egl::EXTENSIONS)
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if client_extensions.split(' ').any(|ext| ext == "EGL_MESA_platform_surfaceless") {
+            if let Some(egl15) = egl.upcast::<egl::EGL1_5>() {
+                let display = unsafe {
+                    egl15.get_platform_display(PLATFORM_SURFACELESS_MESA, egl::DEFAULT_DISPLAY, &[egl::ATTRIB_NONE])
+                };
+                if let Ok(display) = display {
+                    return Ok(display);
+                }
+            }
+        }
+        unsafe { egl.get_display(egl::DEFAULT_DISPLAY) }
+            .ok_or_else(|| "No EGL display available".to_owned())
+    }
+
+    /// Create and make current a context for the first OpenGL version in `config.versions`
+    /// supported by the driver.
+    fn create_context(egl: &Egl, display: egl::Display, config: &ContextConfig) -> Result<egl::Context, String> {
+        egl.bind_api(egl::OPENGL_API)
+            .map_err(|e| format!("Failed to bind the OpenGL API: {}", e))?;
+        let egl_config = egl
+            .choose_first_config(display, &[
+                egl::SURFACE_TYPE, egl::PBUFFER_BIT,
+                egl::RENDERABLE_TYPE, egl::OPENGL_BIT,
+                egl::NONE,
+            ])
+            .map_err(|e| format!("Failed to choose an EGL config: {}", e))?
+            .ok_or_else(|| "No EGL config supports OpenGL".to_owned())?;
+
+        let profile = match config.profile {
+            Profile::Core => egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            Profile::Compatibility => egl::CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT,
+        };
+        let mut errors = Vec::new();
+        let mut context = None;
+        for &(major, minor) in &config.versions {
+            let mut attribs = vec![
+                egl::CONTEXT_MAJOR_VERSION, major as egl::Int,
+                egl::CONTEXT_MINOR_VERSION, minor as egl::Int,
+                egl::CONTEXT_OPENGL_PROFILE_MASK, profile,
+            ];
+            if config.debug {
+                attribs.extend_from_slice(&[egl::CONTEXT_OPENGL_DEBUG, egl::TRUE as egl::Int]);
+            }
+            attribs.push(egl::NONE);
+            match egl.create_context(display, egl_config, None, &attribs) {
+                Ok(ctx) => {
+                    context = Some(ctx);
+                    break;
+                }
+                Err(e) => errors.push(format!("OpenGL {}.{}: {}", major, minor, e)),
+            }
+        }
+        let context = context
+            .ok_or_else(|| format!("Failed to create an OpenGL context ({})", errors.join(", ")))?;
+        egl.make_current(display, None, None, Some(context))
+            .map_err(|e| format!("Failed to make the context current: {}", e))?;
+
+        // Load the OpenGL function pointers
+        gl::load_with(|symbol| {
+            egl.get_proc_address(symbol)
+                .map_or(std::ptr::null(), |f| f as *const c_void)
+        });
+        Ok(context)
+    }
+
+    /// Depth/stencil renderbuffer format and attachment point for the requested bits, if any.
+    fn depth_stencil_format(depth_bits: u8, stencil_bits: u8) -> Option<(GLenum, GLenum)> {
+        match (depth_bits, stencil_bits) {
+            (0, 0) => None,
+            (0, _) => Some((gl::STENCIL_INDEX8, gl::STENCIL_ATTACHMENT)),
+            (1..=16, 0) => Some((gl::DEPTH_COMPONENT16, gl::DEPTH_ATTACHMENT)),
+            (17..=24, 0) => Some((gl::DEPTH_COMPONENT24, gl::DEPTH_ATTACHMENT)),
+            (_, 0) => Some((gl::DEPTH_COMPONENT32F, gl::DEPTH_ATTACHMENT)),
+            (1..=24, _) => Some((gl::DEPTH24_STENCIL8, gl::DEPTH_STENCIL_ATTACHMENT)),
+            (_, _) => Some((gl::DEPTH32F_STENCIL8, gl::DEPTH_STENCIL_ATTACHMENT)),
+        }
+    }
+
+    fn create_renderbuffer(samples: GLsizei, format: GLenum, width: GLsizei, height: GLsizei) -> GLuint {
+        let mut rbo = 0;
+        unsafe {
+            gl::GenRenderbuffers(1, &mut rbo);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, rbo);
+            if samples > 0 {
+                gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples, format, width, height);
+            } else {
+                gl::RenderbufferStorage(gl::RENDERBUFFER, format, width, height);
+            }
+        }
+        rbo
+    }
+
+    fn create_framebuffers(&mut self, config: &ContextConfig) -> Result<(), String> {
+        let samples = config.msaa_samples as GLsizei;
+        let color_format = if config.srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 };
+        unsafe {
+            self.color_rbo = Self::create_renderbuffer(samples, color_format, self.width, self.height);
+            gl::GenFramebuffers(1, &mut self.fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::RENDERBUFFER, self.color_rbo);
+            if let Some((format, attachment)) = Self::depth_stencil_format(config.depth_bits, config.stencil_bits) {
+                self.depth_stencil_rbo = Self::create_renderbuffer(samples, format, self.width, self.height);
+                gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment, gl::RENDERBUFFER, self.depth_stencil_rbo);
+            }
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(format!("Offscreen framebuffer is incomplete: 0x{:x}", status));
+            }
+
+            if samples > 0 {
+                self.resolve_rbo = Self::create_renderbuffer(0, color_format, self.width, self.height);
+                gl::GenFramebuffers(1, &mut self.resolve_fbo);
+                gl::BindFramebuffer(gl::FRAMEBUFFER, self.resolve_fbo);
+                gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::RENDERBUFFER, self.resolve_rbo);
+                let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+                if status != gl::FRAMEBUFFER_COMPLETE {
+                    return Err(format!("Offscreen resolve framebuffer is incomplete: 0x{:x}", status));
+                }
+                gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            }
+
+            if config.srgb {
+                gl::Enable(gl::FRAMEBUFFER_SRGB);
+            }
+
+            // Without a default framebuffer the initial viewport is empty.
+            gl::Viewport(0, 0, self.width, self.height);
+        }
+        Ok(())
+    }
+
+    /// Read back the color attachment as a top-down RGBA image.
+    fn read_frame(&self) -> RgbaImage {
+        let (width, height) = (self.width as u32, self.height as u32);
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+        unsafe {
+            if self.resolve_fbo != 0 {
+                gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+                gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.resolve_fbo);
+                gl::BlitFramebuffer(
+                    0, 0, self.width, self.height,
+                    0, 0, self.width, self.height,
+                    gl::COLOR_BUFFER_BIT,
+                    gl::NEAREST,
+                );
+                gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.fbo);
+                gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.resolve_fbo);
+            } else {
+                gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+            }
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                self.width,
+                self.height,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut c_void,
+            );
+        }
+        // OpenGL rows go bottom-up
+        let img = RgbaImage::from_raw(width, height, pixels).unwrap();
+        image::imageops::flip_vertical(&img)
+    }
+}
+
+impl Drop for HeadlessContext {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteRenderbuffers(1, &self.color_rbo);
+            gl::DeleteRenderbuffers(1, &self.depth_stencil_rbo);
+            gl::DeleteFramebuffers(1, &self.resolve_fbo);
+            gl::DeleteRenderbuffers(1, &self.resolve_rbo);
+        }
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_context(self.display, self.context);
+        let _ = self.egl.terminate(self.display);
+    }
+}
+
+/// Run the app in an offscreen context and return the first `frames` rendered frames,
+/// simulated at 60 frames per second regardless of the time actually spent rendering.
+///
+/// See `run_headless_with_clock`.
+pub fn run_headless<T: OpenGLApp>(app: T, frames: usize) -> Result<Vec<RgbaImage>, String> {
+    run_headless_with_clock(app, FixedStepClock::with_fps(60.0), frames)
+}
+
+/// Call `f` with an offscreen context current, e.g. to test OpenGL calls without an app.
+///
+/// See `run_headless_with_clock`.
+pub fn with_gl<F: FnOnce()>(f: F) -> Result<(), String> {
+    struct Call<F>(Option<F>);
+
+    impl<F: FnOnce()> OpenGLApp for Call<F> {
+        fn initialize(&mut self) {
+            if let Some(f) = self.0.take() {
+                f();
+            }
+        }
+    }
+
+    run_headless(Call(Some(f)), 0).map(|_| ())
+}
+
+/// Run the app in an offscreen context and return the first `frames` rendered frames,
+/// advancing its simulation by the given clock on each frame.
+///
+/// The context is created through EGL, using Mesa's surfaceless platform when available, so no
+/// display server or GPU is required: a software rasterizer such as llvmpipe is enough.
+/// Frames are rendered into a framebuffer object of `app.width()` x `app.height()` pixels
+/// set up after `app.context_config()`, which the app must not unbind. Vsync does not apply.
+///
+/// Concurrent calls wait for each other, since they share the global OpenGL function pointers.
+pub fn run_headless_with_clock<T: OpenGLApp, C: Clock>(
+    mut app: T,
+    mut clock: C,
+    frames: usize,
+) -> Result<Vec<RgbaImage>, String> {
+    // A panicking app, e.g. a failed test assertion, leaves nothing to clean up
+    let _guard = RUN_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let config = app.context_config();
+    let ctx = HeadlessContext::new(app.width() as u32, app.height() as u32, &config)?;
+    if config.debug {
+        debug::enable(&DebugConfig::default());
+    }
+
+    app.initialize();
+
+    let mut simulation = Simulation::default();
+    let mut images = Vec::with_capacity(frames);
+    for _ in 0..frames {
+        let alpha = simulation.frame(&mut app, &mut clock);
+        app.render(alpha);
+        app.render_ui();
+        unsafe {
+            gl::Finish();
+        }
+        images.push(ctx.read_frame());
+    }
+
+    app.cleanup();
+    // Release the app's GL resources while the context is still current.
+    drop(app);
+    drop(ctx);
+    Ok(images)
+}
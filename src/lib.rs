@@ -1,5 +1,9 @@
 use std::time::Instant;
 
+use crate::clock::Clock;
+use crate::context::ContextConfig;
+use crate::glutin::{ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode};
+
 /// trait for OpenGL demo apps.
 pub trait OpenGLApp {
     /// window title
@@ -22,14 +26,62 @@ pub trait OpenGLApp {
         false
     }
 
-    fn resize(&mut self, width: u32, height: u32) {}
+    /// OpenGL context and framebuffer settings.
+    fn context_config(&self) -> ContextConfig {
+        ContextConfig::default()
+    }
+
+    fn resize(&mut self, _width: u32, _height: u32) {}
+
+    /// A key was pressed, or is repeated while held down.
+    fn key_pressed(&mut self, _key: VirtualKeyCode, _modifiers: ModifiersState) {}
+
+    /// A key was released.
+    fn key_released(&mut self, _key: VirtualKeyCode, _modifiers: ModifiersState) {}
+
+    /// The cursor moved to (`x`, `y`), in physical pixels from the top left corner of the window.
+    fn mouse_moved(&mut self, _x: f64, _y: f64, _modifiers: ModifiersState) {}
+
+    /// A mouse button was pressed.
+    fn mouse_pressed(&mut self, _button: MouseButton, _modifiers: ModifiersState) {}
+
+    /// A mouse button was released.
+    fn mouse_released(&mut self, _button: MouseButton, _modifiers: ModifiersState) {}
+
+    /// The mouse wheel or touchpad was scrolled.
+    fn scrolled(&mut self, _delta: MouseScrollDelta, _modifiers: ModifiersState) {}
+
+    /// The window gained or lost the focus.
+    fn focused(&mut self, _focused: bool) {}
+
+    /// A character was typed.
+    fn text_input(&mut self, _c: char, _modifiers: ModifiersState) {}
 
     /// Initialize resources.
     /// The OpenGL context is made current before this function is called.
     fn initialize(&mut self) {}
 
+    /// Fixed simulation time step in seconds, if any. It must be positive.
+    ///
+    /// When set, `update` is called with this time step as many times as needed to catch up
+    /// with the elapsed time, so the simulation is independent of the frame rate.
+    /// Otherwise `update` is called once per frame with the frame duration.
+    fn fixed_timestep(&self) -> Option<f32> {
+        None
+    }
+
+    /// Control the runner's clock, e.g. to pause, step or jump in time in response to input.
+    /// Called once per frame, before the clock ticks, so that changes apply to the same frame.
+    fn control_clock(&mut self, _clock: &mut dyn Clock) {}
+
+    /// Advance the simulation by `dt` seconds.
+    /// Called before rendering each frame, with the time given by the runner's clock.
+    fn update(&mut self, _dt: f32) {}
+
     /// Render a demo frame.
-    fn render(&self) {}
+    /// `alpha` in [0, 1) is how far the elapsed time is between the last simulation step and
+    /// the next one in fixed timestep mode, to interpolate between states. It is 1 otherwise.
+    fn render(&self, _alpha: f32) {}
 
     /// Render a immediate mode ui if any.
     fn render_ui(&self) {}
@@ -38,7 +90,184 @@ pub trait OpenGLApp {
     fn cleanup(&self) {}
 }
 
+/// Drives `OpenGLApp::update` from frame durations, stepping the simulation at the app's
+/// fixed time step when it has one.
+#[derive(Default)]
+pub(crate) struct Simulation {
+    accumulator: f32,
+}
+
+impl Simulation {
+    /// Frame durations of real time clocks are clamped to avoid spiraling when steps take
+    /// longer than real time. Other clocks can jump by any amount, e.g. to render at a given time.
+    const MAX_FRAME_TIME: f32 = 0.25;
+
+    /// Let the app control the clock, tick it and advance the app simulation by the elapsed
+    /// time. Return the interpolation factor to render with.
+    pub(crate) fn frame<T: OpenGLApp>(&mut self, app: &mut T, clock: &mut dyn Clock) -> f32 {
+        app.control_clock(clock);
+        let frame_time = clock.tick();
+        let frame_time = if clock.is_realtime() { frame_time.min(Self::MAX_FRAME_TIME) } else { frame_time };
+        self.advance(app, frame_time)
+    }
+
+    /// Advance the app simulation by `frame_time` seconds and return the interpolation
+    /// factor to render with.
+    ///
+    /// The simulation cannot step back: a negative frame time, e.g. after the clock is rewound,
+    /// drops the time carried over and does not update the app.
+    fn advance<T: OpenGLApp>(&mut self, app: &mut T, frame_time: f32) -> f32 {
+        match app.fixed_timestep() {
+            Some(dt) => {
+                assert!(dt > 0.0, "the fixed time step must be positive, got {}", dt);
+                if frame_time < 0.0 {
+                    self.accumulator = 0.0;
+                    return 0.0;
+                }
+                self.accumulator += frame_time;
+                while self.accumulator >= dt {
+                    app.update(dt);
+                    self.accumulator -= dt;
+                }
+                self.accumulator / dt
+            }
+            None => {
+                if frame_time >= 0.0 {
+                    app.update(frame_time);
+                }
+                1.0
+            }
+        }
+    }
+}
+
+pub mod buffer;
+pub mod clock;
+pub mod context;
+pub mod debug;
 pub mod glutin;
+pub mod headless;
 pub mod vao;
 pub mod textures;
 pub mod shaders;
+pub mod ubo;
+
+// Lets the derive macros refer to `::learnopengl_rs` from within the crate too
+extern crate self as learnopengl_rs;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{FixedStepClock, ManualClock};
+
+    /// Records the time steps it is updated with.
+    struct Steps {
+        timestep: Option<f32>,
+        steps: Vec<f32>,
+    }
+
+    impl OpenGLApp for Steps {
+        fn fixed_timestep(&self) -> Option<f32> {
+            self.timestep
+        }
+
+        fn update(&mut self, dt: f32) {
+            self.steps.push(dt);
+        }
+    }
+
+    #[test]
+    fn fixed_timestep_carries_over() {
+        let mut app = Steps { timestep: Some(0.0625), steps: Vec::new() };
+        let mut simulation = Simulation::default();
+        // 2.5 steps, the half step is carried over to the next frame
+        assert_eq!(simulation.advance(&mut app, 0.15625), 0.5);
+        assert_eq!(app.steps, [0.0625, 0.0625]);
+        assert_eq!(simulation.advance(&mut app, 0.03125), 0.0);
+        assert_eq!(app.steps, [0.0625; 3]);
+        assert_eq!(simulation.advance(&mut app, 0.015625), 0.25);
+        assert_eq!(app.steps.len(), 3);
+    }
+
+    #[test]
+    fn negative_frame_time() {
+        let mut app = Steps { timestep: Some(0.0625), steps: Vec::new() };
+        let mut simulation = Simulation::default();
+        assert_eq!(simulation.advance(&mut app, 0.09375), 0.5);
+        // The carried over half step is dropped rather than going negative
+        assert_eq!(simulation.advance(&mut app, -0.0625), 0.0);
+        assert_eq!(simulation.advance(&mut app, 0.0625), 0.0);
+        assert_eq!(app.steps, [0.0625; 2]);
+
+        let mut app = Steps { timestep: None, steps: Vec::new() };
+        assert_eq!(simulation.advance(&mut app, -0.5), 1.0);
+        assert_eq!(simulation.advance(&mut app, 0.25), 1.0);
+        assert_eq!(app.steps, [0.25]);
+    }
+
+    #[test]
+    fn variable_timestep() {
+        let mut app = Steps { timestep: None, steps: Vec::new() };
+        let mut simulation = Simulation::default();
+        assert_eq!(simulation.advance(&mut app, 0.3), 1.0);
+        assert_eq!(simulation.advance(&mut app, 0.01), 1.0);
+        assert_eq!(app.steps, [0.3, 0.01]);
+    }
+
+    /// Pauses its clock after 2 frames.
+    struct Pausing {
+        frames: usize,
+        steps: Steps,
+    }
+
+    impl OpenGLApp for Pausing {
+        fn fixed_timestep(&self) -> Option<f32> {
+            self.steps.fixed_timestep()
+        }
+
+        fn control_clock(&mut self, clock: &mut dyn Clock) {
+            self.frames += 1;
+            if self.frames == 3 {
+                clock.pause();
+            }
+        }
+
+        fn update(&mut self, dt: f32) {
+            self.steps.update(dt);
+        }
+    }
+
+    #[test]
+    fn clock_jumps_are_not_clamped() {
+        let mut app = Steps { timestep: Some(0.5), steps: Vec::new() };
+        let mut clock = ManualClock::at(2.5);
+        assert_eq!(Simulation::default().frame(&mut app, &mut clock), 0.0);
+        assert_eq!(app.steps, [0.5; 5]);
+    }
+
+    #[test]
+    fn apps_control_the_clock() {
+        let mut app = Pausing { frames: 0, steps: Steps { timestep: None, steps: Vec::new() } };
+        let mut clock = FixedStepClock::new(0.25);
+        let mut simulation = Simulation::default();
+        for _ in 0..4 {
+            simulation.frame(&mut app, &mut clock);
+        }
+        assert_eq!(app.steps.steps, [0.0, 0.25, 0.0, 0.0]);
+        assert!(clock.is_paused());
+    }
+
+    #[test]
+    #[should_panic(expected = "the fixed time step must be positive")]
+    fn zero_timestep() {
+        let mut app = Steps { timestep: Some(0.0), steps: Vec::new() };
+        Simulation::default().advance(&mut app, 0.1);
+    }
+
+    #[test]
+    #[should_panic(expected = "the fixed time step must be positive")]
+    fn nan_timestep() {
+        let mut app = Steps { timestep: Some(f32::NAN), steps: Vec::new() };
+        Simulation::default().advance(&mut app, 0.1);
+    }
+}
@@ -1,10 +1,34 @@
 use std;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ffi::CString;
+use std::path::PathBuf;
 
 use gl::{self, types::*};
+use nalgebra::{Isometry3, Matrix2, Matrix3, Matrix4, Perspective3, Vector2, Vector3, Vector4};
+
+pub mod cache;
+mod error;
+pub mod preprocessor;
+pub mod reflect;
+pub mod reload;
+pub mod validate;
+
+pub use self::error::{parse_log, Diagnostic, DiagnosticLevel, ShaderError};
+
+/// Directory shader source files are read from at runtime.
+pub const SHADER_DIR: &str = "res/shaders";
 
 pub struct Shader {
     id: GLuint,
+    ty: GLenum,
+}
+
+impl Shader {
+    /// Type of the shader, e.g. `gl::VERTEX_SHADER`.
+    pub fn shader_type(&self) -> GLenum {
+        self.ty
+    }
 }
 
 impl Drop for Shader {
@@ -12,7 +36,7 @@ impl Drop for Shader {
     ///
     /// See [glDeleteShader](https://docs.gl/gl3/glDeleteShader) for details.
     fn drop(&mut self) {
-        println!("Dropping shader {}", self.id);
+        log::debug!("Dropping shader {}", self.id);
         unsafe {
             gl::DeleteShader(self.id);
         }
@@ -21,11 +45,91 @@ impl Drop for Shader {
 
 pub struct ShaderProgram {
     pub id: GLuint,
+    /// Uniform locations by name, -1 for names that are not active uniforms.
+    locations: RefCell<HashMap<String, GLint>>,
 }
 
 impl Default for ShaderProgram {
     fn default() -> Self {
-        Self { id: 0 }
+        Self::from_id(0)
+    }
+}
+
+impl ShaderProgram {
+    fn from_id(id: GLuint) -> Self {
+        Self { id, locations: RefCell::new(HashMap::new()) }
+    }
+
+    /// Location of the uniform with the given name, or -1 if the program has no such active
+    /// uniform. Locations are looked up once and cached.
+    ///
+    /// See [glGetUniformLocation](https://docs.gl/gl3/glGetUniformLocation) for details.
+    pub fn uniform_location(&self, name: &str) -> GLint {
+        if let Some(location) = self.locations.borrow().get(name) {
+            return *location;
+        }
+        let location = match CString::new(name) {
+            Ok(c_name) => unsafe { gl::GetUniformLocation(self.id, c_name.as_ptr()) },
+            Err(_) => -1,
+        };
+        self.locations.borrow_mut().insert(name.to_owned(), location);
+        location
+    }
+
+    /// Make the program current and set the value of the uniform with the given name.
+    ///
+    /// In debug builds, setting a name that is not an active uniform of the program is an error,
+    /// most likely a typo or a uniform optimized away by the driver. Release builds ignore it,
+    /// like OpenGL does.
+    pub fn set_uniform<U: Uniform + ?Sized>(&self, name: &str, value: &U) -> Result<(), String> {
+        let location = self.uniform_location(name);
+        if location == -1 {
+            return if cfg!(debug_assertions) {
+                Err(format!("No active uniform named '{}' in shader program {}", name, self.id))
+            } else {
+                Ok(())
+            };
+        }
+        unsafe {
+            gl::UseProgram(self.id);
+            value.set(location);
+        }
+        Ok(())
+    }
+
+    /// Make the uniform block with the given name read from the uniform buffer bound to
+    /// `binding`, see `UniformBuffer::bind`.
+    ///
+    /// See [glUniformBlockBinding](https://docs.gl/gl3/glUniformBlockBinding) for details.
+    pub fn bind_uniform_block(&self, name: &str, binding: GLuint) -> Result<(), String> {
+        let index = match CString::new(name) {
+            Ok(c_name) => unsafe { gl::GetUniformBlockIndex(self.id, c_name.as_ptr()) },
+            Err(_) => gl::INVALID_INDEX,
+        };
+        if index == gl::INVALID_INDEX {
+            return Err(format!("No active uniform block named '{}' in shader program {}", name, self.id));
+        }
+        unsafe {
+            gl::UniformBlockBinding(self.id, index, binding);
+        }
+        Ok(())
+    }
+
+    /// Check whether the program can execute in the current OpenGL state, e.g. that samplers of
+    /// different types are not bound to the same texture unit.
+    ///
+    /// See [glValidateProgram](https://docs.gl/gl3/glValidateProgram) for details.
+    pub fn validate(&self) -> Result<(), ShaderError> {
+        unsafe {
+            gl::ValidateProgram(self.id);
+            let mut status = gl::FALSE as GLint;
+            gl::GetProgramiv(self.id, gl::VALIDATE_STATUS, &mut status);
+            if status != (gl::TRUE as GLint) {
+                let log = info_log(self.id, gl::GetProgramiv, gl::GetProgramInfoLog);
+                return Err(ShaderError::validation(log));
+            }
+        }
+        Ok(())
     }
 }
 
@@ -34,7 +138,7 @@ impl Drop for ShaderProgram {
     ///
     /// See [glDeleteProgram](https://docs.gl/gl3/glDeleteProgram) for details.
     fn drop(&mut self) {
-        println!("Dropping shader program {}", self.id);
+        log::debug!("Dropping shader program {}", self.id);
         unsafe {
             gl::DeleteProgram(self.id);
         }
@@ -42,69 +146,284 @@ impl Drop for ShaderProgram {
 }
 
 /// Compile a shader.
-pub fn compile(src: &str, ty: GLenum) -> Result<Shader, String> {
+pub fn compile(src: &str, ty: GLenum) -> Result<Shader, ShaderError> {
+    compile_files(src, ty, &[])
+}
+
+/// Compile a shader whose GLSL source strings were read from `files`, so that errors refer to
+/// them.
+pub(crate) fn compile_files(src: &str, ty: GLenum, files: &[PathBuf]) -> Result<Shader, ShaderError> {
+    let c_str = CString::new(src.as_bytes())
+        .map_err(|e| ShaderError::InvalidSource { position: e.nul_position() })?;
     unsafe {
-        let id = gl::CreateShader(ty);
-        // Attempt to compile the shader
-        let c_str = CString::new(src.as_bytes()).unwrap();
-        gl::ShaderSource(id, 1, &c_str.as_ptr(), std::ptr::null());
-        gl::CompileShader(id);
+        // Owning the id right away deletes the shader on failure
+        let shader = Shader { id: gl::CreateShader(ty), ty };
+        if shader.id == 0 {
+            return Err(ShaderError::UnsupportedStage { shader_type: ty });
+        }
+        gl::ShaderSource(shader.id, 1, &c_str.as_ptr(), std::ptr::null());
+        gl::CompileShader(shader.id);
 
         // Get the compile status
         let mut status = gl::FALSE as GLint;
-        gl::GetShaderiv(id, gl::COMPILE_STATUS, &mut status);
+        gl::GetShaderiv(shader.id, gl::COMPILE_STATUS, &mut status);
 
         // Fail on error
         if status != (gl::TRUE as GLint) {
-            let mut len = 0;
-            gl::GetShaderiv(id, gl::INFO_LOG_LENGTH, &mut len);
-            let mut buf = Vec::with_capacity(len as usize);
-            buf.set_len((len as usize) - 1); // subtract 1 to skip the trailing null character
-            gl::GetShaderInfoLog(
-                id,
-                len,
-                std::ptr::null_mut(),
-                buf.as_mut_ptr() as *mut GLchar,
-            );
-            let msg = std::str::from_utf8(&buf)
-                .ok()
-                .expect("ShaderInfoLog not valid utf8");
-            Err(msg.to_owned())
+            let log = info_log(shader.id, gl::GetShaderiv, gl::GetShaderInfoLog);
+            Err(ShaderError::compile(ty, src, files, log))
         } else {
-            Ok(Shader { id })
+            Ok(shader)
         }
     }
 }
 
 /// Link the given vertex shader and fragment shader into a shader program.
-pub fn link(vs: &Shader, fs: &Shader) -> Result<ShaderProgram, String> {
-    unsafe {
-        let id = gl::CreateProgram();
-        gl::AttachShader(id, vs.id);
-        gl::AttachShader(id, fs.id);
-        gl::LinkProgram(id);
-        // Get the link status
-        let mut status = gl::FALSE as GLint;
-        gl::GetProgramiv(id, gl::LINK_STATUS, &mut status);
+pub fn link(vs: &Shader, fs: &Shader) -> Result<ShaderProgram, ShaderError> {
+    ProgramBuilder {
+        shaders: vec![vs, fs],
+        ..ProgramBuilder::default()
+    }
+    .link()
+}
 
-        // Fail on error
-        if status != (gl::TRUE as GLint) {
-            let mut len: GLint = 0;
-            gl::GetProgramiv(id, gl::INFO_LOG_LENGTH, &mut len);
-            let mut buf = Vec::with_capacity(len as usize);
-            buf.set_len((len as usize) - 1); // subtract 1 to skip the trailing null character
-            gl::GetProgramInfoLog(
-                id,
-                len,
-                std::ptr::null_mut(),
-                buf.as_mut_ptr() as *mut GLchar,
-            );
-            let msg = std::str::from_utf8(&buf)
-                .ok()
-                .expect("ProgramInfoLog not valid utf8");
-            Err(msg.to_owned())
-        } else {
-            Ok(ShaderProgram { id })
+/// How transform feedback records varyings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeedbackMode {
+    /// All varyings are written to a single buffer.
+    Interleaved,
+    /// Each varying is written to the buffer bound to its own index.
+    Separate,
+}
+
+/// Shaders to link into a program, along with the bindings to set before linking.
+///
+/// Any combination of vertex, tessellation control, tessellation evaluation, geometry and fragment
+/// shaders can be linked, or a compute shader on its own (OpenGL 4.3+).
+pub struct ProgramBuilder<'a> {
+    pub shaders: Vec<&'a Shader>,
+    /// Vertex attribute locations, as (name, location) pairs.
+    /// `layout(location = n)` qualifiers in the shader take precedence.
+    pub attribute_locations: Vec<(String, GLuint)>,
+    /// Fragment shader output color numbers, as (name, color number) pairs.
+    /// `layout(location = n)` qualifiers in the shader take precedence.
+    pub frag_data_locations: Vec<(String, GLuint)>,
+    /// Outputs of the last vertex processing stage to record with transform feedback.
+    pub feedback_varyings: Vec<String>,
+    pub feedback_mode: FeedbackMode,
+    /// Hint that the binary of the program will be retrieved, see `cache::ProgramCache`.
+    pub binary_retrievable: bool,
+}
+
+impl Default for ProgramBuilder<'_> {
+    fn default() -> Self {
+        Self {
+            shaders: Vec::new(),
+            attribute_locations: Vec::new(),
+            frag_data_locations: Vec::new(),
+            feedback_varyings: Vec::new(),
+            feedback_mode: FeedbackMode::Interleaved,
+            binary_retrievable: false,
         }
     }
 }
+
+impl ProgramBuilder<'_> {
+    /// Attach the shaders, set the bindings and link the program.
+    ///
+    /// See [glLinkProgram](https://docs.gl/gl4/glLinkProgram) for details.
+    pub fn link(&self) -> Result<ShaderProgram, ShaderError> {
+        let c_name = |name: &str| {
+            CString::new(name)
+                .map_err(|_| ShaderError::link(format!("error: name {:?} contains a NUL byte", name)))
+        };
+        let attribute_locations = self
+            .attribute_locations
+            .iter()
+            .map(|(name, location)| Ok((c_name(name)?, *location)))
+            .collect::<Result<Vec<_>, ShaderError>>()?;
+        let frag_data_locations = self
+            .frag_data_locations
+            .iter()
+            .map(|(name, color)| Ok((c_name(name)?, *color)))
+            .collect::<Result<Vec<_>, ShaderError>>()?;
+        let feedback_varyings = self
+            .feedback_varyings
+            .iter()
+            .map(|name| c_name(name))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        unsafe {
+            let program = ShaderProgram::from_id(gl::CreateProgram());
+            for shader in &self.shaders {
+                gl::AttachShader(program.id, shader.id);
+            }
+            for (name, location) in &attribute_locations {
+                gl::BindAttribLocation(program.id, *location, name.as_ptr());
+            }
+            for (name, color) in &frag_data_locations {
+                gl::BindFragDataLocation(program.id, *color, name.as_ptr());
+            }
+            if !feedback_varyings.is_empty() {
+                let names: Vec<_> = feedback_varyings.iter().map(|name| name.as_ptr()).collect();
+                let mode = match self.feedback_mode {
+                    FeedbackMode::Interleaved => gl::INTERLEAVED_ATTRIBS,
+                    FeedbackMode::Separate => gl::SEPARATE_ATTRIBS,
+                };
+                gl::TransformFeedbackVaryings(program.id, names.len() as GLsizei, names.as_ptr(), mode);
+            }
+            if self.binary_retrievable {
+                gl::ProgramParameteri(program.id, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as GLint);
+            }
+            gl::LinkProgram(program.id);
+            // The program keeps the linked code, the shaders can be deleted
+            for shader in &self.shaders {
+                gl::DetachShader(program.id, shader.id);
+            }
+
+            // Get the link status
+            let mut status = gl::FALSE as GLint;
+            gl::GetProgramiv(program.id, gl::LINK_STATUS, &mut status);
+
+            // Fail on error
+            if status != (gl::TRUE as GLint) {
+                let log = info_log(program.id, gl::GetProgramiv, gl::GetProgramInfoLog);
+                Err(ShaderError::link(log))
+            } else {
+                Ok(program)
+            }
+        }
+    }
+}
+
+/// Read the info log of a shader or program, given the matching `glGet*iv` and
+/// `glGet*InfoLog` functions.
+unsafe fn info_log(
+    id: GLuint,
+    get_iv: unsafe fn(GLuint, GLenum, *mut GLint),
+    get_info_log: unsafe fn(GLuint, GLsizei, *mut GLsizei, *mut GLchar),
+) -> String {
+    let mut len = 0;
+    get_iv(id, gl::INFO_LOG_LENGTH, &mut len);
+    let mut buf = vec![0u8; len.max(1) as usize];
+    let mut written = 0;
+    get_info_log(id, buf.len() as GLsizei, &mut written, buf.as_mut_ptr() as *mut GLchar);
+    buf.truncate(written.max(0) as usize);
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+/// A value that can be set to a uniform of a shader program.
+pub trait Uniform {
+    /// Set the value of the uniform at `location` of the current program.
+    ///
+    /// # Safety
+    /// A program must be current and `location` must be one of its uniform locations of a type
+    /// compatible with `Self`.
+    unsafe fn set(&self, location: GLint);
+}
+
+impl Uniform for f32 {
+    unsafe fn set(&self, location: GLint) {
+        gl::Uniform1f(location, *self);
+    }
+}
+
+impl Uniform for i32 {
+    unsafe fn set(&self, location: GLint) {
+        gl::Uniform1i(location, *self);
+    }
+}
+
+impl Uniform for u32 {
+    unsafe fn set(&self, location: GLint) {
+        gl::Uniform1ui(location, *self);
+    }
+}
+
+impl Uniform for bool {
+    unsafe fn set(&self, location: GLint) {
+        gl::Uniform1i(location, *self as GLint);
+    }
+}
+
+/// Implement `Uniform` for a nalgebra type and slices of it, given the `glUniform*v`
+/// function taking a pointer to its components.
+macro_rules! impl_uniform_vector {
+    ($ty:ty, $component:ty, $uniform_v:path) => {
+        impl Uniform for $ty {
+            unsafe fn set(&self, location: GLint) {
+                $uniform_v(location, 1, self.as_ptr());
+            }
+        }
+
+        impl Uniform for [$ty] {
+            unsafe fn set(&self, location: GLint) {
+                $uniform_v(location, self.len() as GLsizei, self.as_ptr() as *const $component);
+            }
+        }
+    };
+}
+
+macro_rules! impl_uniform_matrix {
+    ($ty:ty, $uniform_matrix_v:path) => {
+        impl Uniform for $ty {
+            unsafe fn set(&self, location: GLint) {
+                $uniform_matrix_v(location, 1, gl::FALSE, self.as_ptr());
+            }
+        }
+
+        impl Uniform for [$ty] {
+            unsafe fn set(&self, location: GLint) {
+                $uniform_matrix_v(location, self.len() as GLsizei, gl::FALSE, self.as_ptr() as *const GLfloat);
+            }
+        }
+    };
+}
+
+impl_uniform_vector!(Vector2<f32>, GLfloat, gl::Uniform2fv);
+impl_uniform_vector!(Vector3<f32>, GLfloat, gl::Uniform3fv);
+impl_uniform_vector!(Vector4<f32>, GLfloat, gl::Uniform4fv);
+impl_uniform_vector!(Vector2<i32>, GLint, gl::Uniform2iv);
+impl_uniform_vector!(Vector3<i32>, GLint, gl::Uniform3iv);
+impl_uniform_vector!(Vector4<i32>, GLint, gl::Uniform4iv);
+impl_uniform_matrix!(Matrix2<f32>, gl::UniformMatrix2fv);
+impl_uniform_matrix!(Matrix3<f32>, gl::UniformMatrix3fv);
+impl_uniform_matrix!(Matrix4<f32>, gl::UniformMatrix4fv);
+
+impl Uniform for [f32] {
+    unsafe fn set(&self, location: GLint) {
+        gl::Uniform1fv(location, self.len() as GLsizei, self.as_ptr());
+    }
+}
+
+impl Uniform for [i32] {
+    unsafe fn set(&self, location: GLint) {
+        gl::Uniform1iv(location, self.len() as GLsizei, self.as_ptr());
+    }
+}
+
+impl Uniform for [u32] {
+    unsafe fn set(&self, location: GLint) {
+        gl::Uniform1uiv(location, self.len() as GLsizei, self.as_ptr());
+    }
+}
+
+impl<T, const N: usize> Uniform for [T; N] where [T]: Uniform {
+    unsafe fn set(&self, location: GLint) {
+        self[..].set(location);
+    }
+}
+
+/// Set as a `mat4`.
+impl Uniform for Isometry3<f32> {
+    unsafe fn set(&self, location: GLint) {
+        self.to_homogeneous().set(location);
+    }
+}
+
+/// Set as a `mat4`.
+impl Uniform for Perspective3<f32> {
+    unsafe fn set(&self, location: GLint) {
+        self.as_matrix().set(location);
+    }
+}
@@ -0,0 +1,185 @@
+use std::ffi::{c_void, CStr};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use gl::{self, types::*};
+
+use crate::context::has_feature;
+
+use super::preprocessor::PreprocessedSource;
+use super::{info_log, ProgramBuilder, ShaderError, ShaderProgram};
+
+/// Bumped when the layout of cache files changes, so that old files are ignored.
+const CACHE_VERSION: u32 = 1;
+
+/// Stores linked programs on disk to skip compiling and linking them on later runs.
+///
+/// Programs are keyed by a hash of their preprocessed sources and of the vendor, renderer and
+/// version strings of the driver, since binaries are only valid for the driver that produced them.
+/// A binary the driver rejects anyway, e.g. after an update that kept the version string, is
+/// deleted and the program is built from its sources again.
+///
+/// Programs whose bindings are set with `ProgramBuilder` before linking are not cached.
+pub struct ProgramCache {
+    /// Directory the binaries are stored in, created when needed.
+    pub dir: PathBuf,
+}
+
+impl Default for ProgramCache {
+    fn default() -> Self {
+        Self { dir: PathBuf::from("target/shader-cache") }
+    }
+}
+
+impl ProgramCache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Whether the current context can retrieve and load program binaries
+    /// (OpenGL 4.1 or `GL_ARB_get_program_binary`, with at least one binary format).
+    pub fn is_supported() -> bool {
+        if !has_feature((4, 1), "GL_ARB_get_program_binary") {
+            return false;
+        }
+        let mut formats = 0;
+        unsafe {
+            gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats);
+        }
+        formats > 0
+    }
+
+    /// Link a program from the given (source, shader type) stages, loading its binary from the
+    /// cache if possible, or compiling and linking the sources and storing the binary otherwise.
+    ///
+    /// The cache is bypassed when the context does not support program binaries. Failing to
+    /// read or write cache files is logged and does not fail the build.
+    pub fn link(&self, stages: &[(&PreprocessedSource, GLenum)]) -> Result<ShaderProgram, ShaderError> {
+        if !Self::is_supported() {
+            return Self::build(stages, false);
+        }
+        let path = self.path(stages);
+        if let Some(program) = Self::load(&path) {
+            log::debug!("Loaded shader program {} from {}", program.id, path.display());
+            return Ok(program);
+        }
+        let program = Self::build(stages, true)?;
+        if let Err(e) = Self::store(&path, &program) {
+            log::warn!("Failed to cache shader program {} to {}: {}", program.id, path.display(), e);
+        }
+        Ok(program)
+    }
+
+    /// Delete all the cached binaries.
+    pub fn clear(&self) -> io::Result<()> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    fn build(
+        stages: &[(&PreprocessedSource, GLenum)],
+        binary_retrievable: bool,
+    ) -> Result<ShaderProgram, ShaderError> {
+        let shaders = stages
+            .iter()
+            .map(|(source, ty)| source.compile(*ty))
+            .collect::<Result<Vec<_>, _>>()?;
+        ProgramBuilder {
+            shaders: shaders.iter().collect(),
+            binary_retrievable,
+            ..ProgramBuilder::default()
+        }
+        .link()
+    }
+
+    /// Path of the cache file of the program built from `stages` with the current driver.
+    fn path(&self, stages: &[(&PreprocessedSource, GLenum)]) -> PathBuf {
+        let mut hash = Fnv1a::default();
+        hash.write(&CACHE_VERSION.to_le_bytes());
+        for name in [gl::VENDOR, gl::RENDERER, gl::VERSION] {
+            let value = unsafe { gl::GetString(name) };
+            if !value.is_null() {
+                hash.write(unsafe { CStr::from_ptr(value as *const _) }.to_bytes());
+            }
+            hash.write(&[0]);
+        }
+        for (source, ty) in stages {
+            hash.write(&ty.to_le_bytes());
+            hash.write(source.code.as_bytes());
+            hash.write(&[0]);
+        }
+        self.dir.join(format!("{:016x}.bin", hash.0))
+    }
+
+    /// Load the binary stored at `path`, deleting the file if the driver rejects it.
+    fn load(path: &Path) -> Option<ShaderProgram> {
+        let data = fs::read(path).ok()?;
+        let rejected = |reason: &str| {
+            log::info!("Discarding cached shader program {}: {}", path.display(), reason);
+            let _ = fs::remove_file(path);
+            None
+        };
+        if data.len() < 4 {
+            return rejected("truncated file");
+        }
+        let (format, binary) = data.split_at(4);
+        let format = u32::from_le_bytes([format[0], format[1], format[2], format[3]]);
+        unsafe {
+            let program = ShaderProgram::from_id(gl::CreateProgram());
+            gl::ProgramBinary(program.id, format, binary.as_ptr() as *const c_void, binary.len() as GLsizei);
+            let mut status = gl::FALSE as GLint;
+            gl::GetProgramiv(program.id, gl::LINK_STATUS, &mut status);
+            if status != (gl::TRUE as GLint) {
+                return rejected(&info_log(program.id, gl::GetProgramiv, gl::GetProgramInfoLog));
+            }
+            Some(program)
+        }
+    }
+
+    /// Store the binary of `program` at `path`, as its format followed by the binary itself.
+    fn store(path: &Path, program: &ShaderProgram) -> io::Result<()> {
+        let mut len = 0;
+        unsafe {
+            gl::GetProgramiv(program.id, gl::PROGRAM_BINARY_LENGTH, &mut len);
+        }
+        if len <= 0 {
+            return Err(io::Error::other("the driver returned no program binary"));
+        }
+        let mut data = vec![0u8; 4 + len as usize];
+        let (mut written, mut format) = (0, 0);
+        unsafe {
+            gl::GetProgramBinary(program.id, len, &mut written, &mut format,
+                                 data[4..].as_mut_ptr() as *mut c_void);
+        }
+        data[..4].copy_from_slice(&format.to_le_bytes());
+        data.truncate(4 + written.max(0) as usize);
+
+        // Write to a temporary file first, so that other processes never read a partial binary
+        fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&tmp, &data)?;
+        fs::rename(&tmp, path).inspect_err(|_| {
+            let _ = fs::remove_file(&tmp);
+        })
+    }
+}
+
+/// 64-bit FNV-1a, whose output is stable across runs and Rust versions unlike `DefaultHasher`.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
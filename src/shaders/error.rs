@@ -0,0 +1,342 @@
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+use gl::{self, types::*};
+
+use super::preprocessor::line_locations;
+
+/// Severity of a diagnostic reported by the shader compiler or linker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagnosticLevel {
+    Error,
+    Warning,
+    /// Log lines that are neither errors nor warnings, e.g. headers or summaries.
+    Info,
+}
+
+/// A message of a compile, link or validation log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub level: DiagnosticLevel,
+    /// GLSL source string number the message refers to.
+    pub source_string: Option<usize>,
+    /// File the source string was read from, when known.
+    pub file: Option<PathBuf>,
+    /// Line number, starting at 1.
+    pub line: Option<usize>,
+    /// Column number, starting at 1. Only reported by some drivers.
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+/// Error building a shader or shader program.
+///
+/// The `Display` output lists the diagnostics of the driver log, with an excerpt of the
+/// offending source lines for compile errors. `Debug` prints the same report, so that
+/// `unwrap` and `expect` show readable errors too.
+pub enum ShaderError {
+    /// The source contains a NUL byte at `position`, which OpenGL cannot take.
+    InvalidSource { position: usize },
+    /// Shaders of this type are not supported, e.g. compute shaders before OpenGL 4.3.
+    UnsupportedStage { shader_type: GLenum },
+    /// The preprocessor could not assemble the source, e.g. because of a missing include.
+    Preprocess(String),
+    Compile {
+        shader_type: GLenum,
+        /// The code given to the compiler.
+        source: String,
+        /// Files of the GLSL source strings, by number. Empty if unknown.
+        files: Vec<PathBuf>,
+        log: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+    Link { log: String, diagnostics: Vec<Diagnostic> },
+    Validation { log: String, diagnostics: Vec<Diagnostic> },
+}
+
+impl ShaderError {
+    pub(crate) fn compile(shader_type: GLenum, source: &str, files: &[PathBuf], log: String) -> Self {
+        let mut diagnostics = parse_log(&log);
+        for diagnostic in &mut diagnostics {
+            diagnostic.file = diagnostic.source_string.and_then(|i| files.get(i)).cloned();
+        }
+        ShaderError::Compile {
+            shader_type,
+            source: source.to_owned(),
+            files: files.to_vec(),
+            log,
+            diagnostics,
+        }
+    }
+
+    pub(crate) fn link(log: String) -> Self {
+        let diagnostics = parse_log(&log);
+        ShaderError::Link { log, diagnostics }
+    }
+
+    pub(crate) fn validation(log: String) -> Self {
+        let diagnostics = parse_log(&log);
+        ShaderError::Validation { log, diagnostics }
+    }
+
+    /// The diagnostics parsed from the driver log, empty for errors not reported by the driver.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match self {
+            ShaderError::Compile { diagnostics, .. }
+            | ShaderError::Link { diagnostics, .. }
+            | ShaderError::Validation { diagnostics, .. } => diagnostics,
+            ShaderError::InvalidSource { .. }
+            | ShaderError::UnsupportedStage { .. }
+            | ShaderError::Preprocess(_) => &[],
+        }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::InvalidSource { position } => {
+                write!(f, "shader source contains a NUL byte at offset {}", position)
+            }
+            ShaderError::UnsupportedStage { shader_type } => {
+                write!(f, "{} shaders are not supported", shader_type_name(*shader_type))
+            }
+            ShaderError::Preprocess(msg) => write!(f, "failed to preprocess shader: {}", msg),
+            ShaderError::Compile { shader_type, source, diagnostics, .. } => {
+                write!(f, "failed to compile {} shader", shader_type_name(*shader_type))?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n{}", diagnostic)?;
+                    if let (Some(string), Some(line)) = (diagnostic.source_string, diagnostic.line) {
+                        if let Some(text) = source_line(source, string, line) {
+                            write_excerpt(f, line, text, diagnostic.column)?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            ShaderError::Link { diagnostics, .. } => {
+                write!(f, "failed to link shader program")?;
+                diagnostics.iter().try_for_each(|d| write!(f, "\n{}", d))
+            }
+            ShaderError::Validation { diagnostics, .. } => {
+                write!(f, "shader program validation failed")?;
+                diagnostics.iter().try_for_each(|d| write!(f, "\n{}", d))
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Error for ShaderError {}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.file, self.source_string) {
+            (Some(file), _) => write!(f, "{}:", file.display())?,
+            (None, Some(string)) => write!(f, "<string {}>:", string)?,
+            (None, None) => {}
+        }
+        if let Some(line) = self.line {
+            write!(f, "{}:", line)?;
+        }
+        if let Some(column) = self.column {
+            write!(f, "{}:", column)?;
+        }
+        if self.source_string.is_some() {
+            write!(f, " ")?;
+        }
+        match self.level {
+            DiagnosticLevel::Error => write!(f, "error: ")?,
+            DiagnosticLevel::Warning => write!(f, "warning: ")?,
+            DiagnosticLevel::Info => {}
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+fn shader_type_name(shader_type: GLenum) -> &'static str {
+    match shader_type {
+        gl::VERTEX_SHADER => "vertex",
+        gl::TESS_CONTROL_SHADER => "tessellation control",
+        gl::TESS_EVALUATION_SHADER => "tessellation evaluation",
+        gl::GEOMETRY_SHADER => "geometry",
+        gl::FRAGMENT_SHADER => "fragment",
+        gl::COMPUTE_SHADER => "compute",
+        _ => "unknown",
+    }
+}
+
+/// Write a source line along with a caret under `column`, or under the whole line if the column
+/// is unknown.
+fn write_excerpt(f: &mut fmt::Formatter, line: usize, text: &str, column: Option<usize>) -> fmt::Result {
+    let gutter = " ".repeat(line.to_string().len());
+    // Keep tabs so that the caret lines up with the text
+    let indent = |text: &str| -> String {
+        text.chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect()
+    };
+    let (start, len) = match column {
+        Some(column) => {
+            let start = text.char_indices().nth(column.saturating_sub(1)).map_or(text.len(), |(i, _)| i);
+            (start, 1)
+        }
+        None => {
+            let start = text.len() - text.trim_start().len();
+            (start, text.trim().chars().count().max(1))
+        }
+    };
+    write!(f, "\n{} |\n{} | {}\n{} | {}{}", gutter, line, text, gutter, indent(&text[..start]), "^".repeat(len))
+}
+
+/// The text of line `line` of GLSL source string `string` in `source`, following the `#line`
+/// directives the preprocessor inserts.
+fn source_line(source: &str, string: usize, line: usize) -> Option<&str> {
+    line_locations(source).find(|(location, _)| *location == Some((string, line))).map(|(_, text)| text)
+}
+
+/// Parse a driver info log into diagnostics, one per non-empty line.
+///
+/// Understands the formats of Mesa (`0:12(5): error: msg`), NVIDIA (`0(12) : error C0000: msg`)
+/// and AMD (`ERROR: 0:12: msg`). Lines in other formats are kept whole as messages without
+/// location.
+pub fn parse_log(log: &str) -> Vec<Diagnostic> {
+    log.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(parse_line)
+        .collect()
+}
+
+fn parse_line(line: &str) -> Diagnostic {
+    // AMD puts the level first, Mesa and NVIDIA after the location
+    let (level, rest) = match level_prefix(line) {
+        Some((level, rest)) => (Some(level), rest),
+        None => (None, line),
+    };
+    let (location, rest) = match location_prefix(rest) {
+        Some((location, rest)) => (Some(location), rest),
+        None => (None, rest),
+    };
+    let (level, message) = match level {
+        Some(level) => (level, rest),
+        None => level_prefix(rest).unwrap_or((DiagnosticLevel::Info, rest)),
+    };
+    Diagnostic {
+        level,
+        source_string: location.map(|(string, _, _)| string),
+        file: None,
+        line: location.map(|(_, line, _)| line),
+        column: location.and_then(|(_, _, column)| column),
+        message: message.to_owned(),
+    }
+}
+
+/// Split `error: `, `error C0000: ` or `ERROR: ` (and their warning counterparts) off `s`.
+fn level_prefix(s: &str) -> Option<(DiagnosticLevel, &str)> {
+    for (word, level) in [("error", DiagnosticLevel::Error), ("warning", DiagnosticLevel::Warning)] {
+        let rest = match s.get(..word.len()) {
+            Some(w) if w.eq_ignore_ascii_case(word) => &s[word.len()..],
+            _ => continue,
+        };
+        if rest.starts_with(':') || rest.starts_with(' ') {
+            return Some((level, rest.trim_start_matches(':').trim_start()));
+        }
+    }
+    None
+}
+
+/// Source string number, line and column of a log line.
+type Location = (usize, usize, Option<usize>);
+
+/// Split a `string:line(column):`, `string:line:` or `string(line) :` location off `s`.
+fn location_prefix(s: &str) -> Option<(Location, &str)> {
+    let (string, rest) = number(s)?;
+    let (line, column, rest) = if let Some(rest) = rest.strip_prefix(':') {
+        let (line, rest) = number(rest)?;
+        match rest.strip_prefix('(') {
+            Some(rest) => {
+                let (column, rest) = number(rest)?;
+                (line, Some(column), rest.strip_prefix(')')?)
+            }
+            None => (line, None, rest),
+        }
+    } else {
+        let (line, rest) = number(rest.strip_prefix('(')?)?;
+        (line, None, rest.strip_prefix(')')?)
+    };
+    let rest = rest.trim_start().strip_prefix(':')?;
+    Some(((string, line, column), rest.trim_start()))
+}
+
+fn number(s: &str) -> Option<(usize, &str)> {
+    let len = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    Some((s[..len].parse().ok()?, &s[len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn located(level: DiagnosticLevel, line: usize, column: Option<usize>, message: &str) -> Diagnostic {
+        Diagnostic {
+            level,
+            source_string: Some(0),
+            file: None,
+            line: Some(line),
+            column,
+            message: message.to_owned(),
+        }
+    }
+
+    #[test]
+    fn parse_mesa_log() {
+        let log = "0:12(5): error: `foo' undeclared\n0:3(1): warning: unused variable\n";
+        assert_eq!(parse_log(log), vec![
+            located(DiagnosticLevel::Error, 12, Some(5), "`foo' undeclared"),
+            located(DiagnosticLevel::Warning, 3, Some(1), "unused variable"),
+        ]);
+    }
+
+    #[test]
+    fn parse_nvidia_log() {
+        let log = "0(12) : error C1008: undefined variable \"foo\"";
+        assert_eq!(parse_log(log), vec![
+            located(DiagnosticLevel::Error, 12, None, "C1008: undefined variable \"foo\""),
+        ]);
+    }
+
+    #[test]
+    fn parse_amd_log() {
+        let log = "ERROR: 0:12: 'foo' : undeclared identifier\nERROR: 1 compilation errors.  No code generated.";
+        let diagnostics = parse_log(log);
+        assert_eq!(diagnostics[0], located(DiagnosticLevel::Error, 12, None, "'foo' : undeclared identifier"));
+        assert_eq!(diagnostics[1].line, None);
+        assert_eq!(diagnostics[1].message, "1 compilation errors.  No code generated.");
+    }
+
+    #[test]
+    fn parse_unlocated_lines() {
+        let diagnostics = parse_log("error: linking with uncompiled shader\nVertex info");
+        assert_eq!(diagnostics[0].level, DiagnosticLevel::Error);
+        assert_eq!(diagnostics[0].message, "linking with uncompiled shader");
+        assert_eq!(diagnostics[1].level, DiagnosticLevel::Info);
+        assert_eq!(diagnostics[1].message, "Vertex info");
+    }
+
+    #[test]
+    fn display_excerpt() {
+        let source = "#version 330 core\n#line 1 0\nvoid main() {\n    foo = 1.0;\n}\n";
+        let files = vec![PathBuf::from("a.fs")];
+        let error = ShaderError::compile(gl::FRAGMENT_SHADER, source, &files, "0:2(5): error: `foo' undeclared".to_owned());
+        assert_eq!(error.to_string(), "failed to compile fragment shader\n\
+                                       a.fs:2:5: error: `foo' undeclared\n  \
+                                       |\n\
+                                       2 |     foo = 1.0;\n  \
+                                       |     ^");
+    }
+}
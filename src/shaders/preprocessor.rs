@@ -0,0 +1,242 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use gl::types::*;
+
+use super::{compile_files, Shader, ShaderError, SHADER_DIR};
+
+/// Expands `#include "file"` directives, injects `#define`s and sets the `#version` of GLSL
+/// sources.
+///
+/// Each source file gets its own GLSL source string number through `#line` directives, so that
+/// locations in compile errors can be mapped back to the original files.
+pub struct Preprocessor {
+    /// Directory `#include` paths are resolved from.
+    pub include_dir: PathBuf,
+    /// Macros defined right after the `#version` directive, as (name, value) pairs.
+    pub defines: Vec<(String, String)>,
+    /// Version to compile with, e.g. `"330 core"`, replacing the one declared by the source.
+    pub version: Option<String>,
+}
+
+impl Default for Preprocessor {
+    fn default() -> Self {
+        Self {
+            include_dir: PathBuf::from(SHADER_DIR),
+            defines: Vec::new(),
+            version: None,
+        }
+    }
+}
+
+/// The output of the preprocessor.
+pub struct PreprocessedSource {
+    /// GLSL code ready to be compiled.
+    pub code: String,
+    /// Files the code was assembled from, indexed by GLSL source string number.
+    pub files: Vec<PathBuf>,
+}
+
+impl Preprocessor {
+    /// Preprocess the shader file at `path`, resolved from `include_dir` when relative.
+    pub fn preprocess_file<P: AsRef<Path>>(&self, path: P) -> Result<PreprocessedSource, String> {
+        let path = self.include_dir.join(path);
+        let src = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.preprocess(&src, path)
+    }
+
+    /// Preprocess GLSL code, using `name` to refer to it in `#line` mappings.
+    pub fn preprocess<P: Into<PathBuf>>(&self, src: &str, name: P) -> Result<PreprocessedSource, String> {
+        self.preprocess_tracked(src, name, &mut Vec::new())
+    }
+
+    /// Like `preprocess`, also recording in `includes` every file it includes, including the
+    /// ones it fails to read, e.g. to watch them for changes once preprocessing fails.
+    pub(crate) fn preprocess_tracked<P: Into<PathBuf>>(
+        &self,
+        src: &str,
+        name: P,
+        includes: &mut Vec<PathBuf>,
+    ) -> Result<PreprocessedSource, String> {
+        let mut out = PreprocessedSource {
+            code: String::with_capacity(src.len()),
+            files: Vec::new(),
+        };
+
+        // The #version directive must come first, followed by the injected defines.
+        let declared_version = src
+            .lines()
+            .find_map(|line| directive(line, "version"))
+            .map(|v| v.to_owned());
+        if let Some(version) = self.version.as_ref().or(declared_version.as_ref()) {
+            writeln!(out.code, "#version {}", version).unwrap();
+        }
+        for (name, value) in &self.defines {
+            writeln!(out.code, "#define {} {}", name, value).unwrap();
+        }
+
+        self.expand(src, name.into(), &mut out, &mut Vec::new(), includes)?;
+        Ok(out)
+    }
+
+    /// Append `src` to `out`, expanding its includes. `stack` holds the files being expanded,
+    /// `includes` all the files included so far.
+    fn expand(
+        &self,
+        src: &str,
+        path: PathBuf,
+        out: &mut PreprocessedSource,
+        stack: &mut Vec<PathBuf>,
+        includes: &mut Vec<PathBuf>,
+    ) -> Result<(), String> {
+        let index = out.files.len();
+        out.files.push(path.clone());
+        stack.push(path.clone());
+
+        writeln!(out.code, "#line 1 {}", index).unwrap();
+        for (i, line) in src.lines().enumerate() {
+            if directive(line, "version").is_some() {
+                // Keep line numbers in sync
+                out.code.push('\n');
+            } else if let Some(arg) = directive(line, "include") {
+                let include = parse_include(arg)
+                    .ok_or_else(|| format!("{}:{}: malformed #include {}", path.display(), i + 1, arg))?;
+                let include_path = self.include_dir.join(include);
+                if stack.contains(&include_path) {
+                    return Err(format!("{}:{}: recursive #include \"{}\"", path.display(), i + 1, include));
+                }
+                includes.push(include_path.clone());
+                let include_src = fs::read_to_string(&include_path).map_err(|e| {
+                    format!("{}:{}: cannot include {}: {}", path.display(), i + 1, include_path.display(), e)
+                })?;
+                self.expand(&include_src, include_path, out, stack, includes)?;
+                writeln!(out.code, "#line {} {}", i + 2, index).unwrap();
+            } else {
+                out.code.push_str(line);
+                out.code.push('\n');
+            }
+        }
+
+        stack.pop();
+        Ok(())
+    }
+
+    /// Preprocess and compile the shader file at `path`.
+    /// Locations in the compile errors refer to the original files.
+    pub fn compile_file<P: AsRef<Path>>(&self, path: P, ty: GLenum) -> Result<Shader, ShaderError> {
+        let src = self.preprocess_file(path).map_err(ShaderError::Preprocess)?;
+        src.compile(ty)
+    }
+}
+
+impl PreprocessedSource {
+    /// Compile the code as a shader of the given type.
+    /// Locations in the compile errors refer to the original files.
+    pub fn compile(&self, ty: GLenum) -> Result<Shader, ShaderError> {
+        compile_files(&self.code, ty, &self.files)
+    }
+}
+
+/// The argument of a `#name` preprocessor directive, if `line` is one.
+fn directive<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start().strip_prefix(name)?;
+    if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+        Some(rest.trim())
+    } else {
+        None
+    }
+}
+
+/// The GLSL source string number and line of each line of `code`, following its `#line`
+/// directives, which have no location themselves.
+pub(crate) fn line_locations(code: &str) -> impl Iterator<Item = (Option<(usize, usize)>, &str)> {
+    let (mut string, mut line) = (0, 1);
+    code.lines().map(move |text| {
+        // Since GLSL 3.30, the line following `#line n` is line n
+        let mut args = directive(text, "line").into_iter().flat_map(|args| args.split_whitespace());
+        if let Some(Ok(n)) = args.next().map(str::parse) {
+            line = n;
+            if let Some(Ok(s)) = args.next().map(str::parse) {
+                string = s;
+            }
+            return (None, text);
+        }
+        line += 1;
+        (Some((string, line - 1)), text)
+    })
+}
+
+/// The path of an `#include "path"` or `#include <path>` directive.
+fn parse_include(arg: &str) -> Option<&str> {
+    let arg = arg.trim();
+    arg.strip_prefix('"')
+        .and_then(|a| a.strip_suffix('"'))
+        .or_else(|| arg.strip_prefix('<').and_then(|a| a.strip_suffix('>')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A preprocessor including files from a new directory holding `files`.
+    fn preprocessor(name: &str, files: &[(&str, &str)]) -> Preprocessor {
+        let dir = std::env::temp_dir().join(format!("learnopengl-preprocessor-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (file, src) in files {
+            fs::write(dir.join(file), src).unwrap();
+        }
+        Preprocessor { include_dir: dir, ..Preprocessor::default() }
+    }
+
+    #[test]
+    fn expand_includes() {
+        let preprocessor = Preprocessor {
+            defines: vec![("LIGHTS".to_owned(), "4".to_owned())],
+            version: Some("410 core".to_owned()),
+            ..preprocessor("expand", &[("common.glsl", "float a;\n#include <inner.glsl>\n"), ("inner.glsl", "float b;\n")])
+        };
+        let src = "#version 330 core\n#include \"common.glsl\"\nvoid main() {}\n";
+        let out = preprocessor.preprocess(src, "main.vs").unwrap();
+        assert_eq!(out.code, "#version 410 core\n#define LIGHTS 4\n\
+                              #line 1 0\n\n\
+                              #line 1 1\nfloat a;\n\
+                              #line 1 2\nfloat b;\n\
+                              #line 3 1\n\
+                              #line 3 0\nvoid main() {}\n");
+        let dir = &preprocessor.include_dir;
+        assert_eq!(out.files, [PathBuf::from("main.vs"), dir.join("common.glsl"), dir.join("inner.glsl")]);
+    }
+
+    #[test]
+    fn recursive_include() {
+        let preprocessor = preprocessor("recursive", &[("a.glsl", "#include \"b.glsl\""), ("b.glsl", "\n#include \"a.glsl\"")]);
+        let error = preprocessor.preprocess_file("a.glsl").err().unwrap();
+        assert!(error.ends_with("b.glsl:2: recursive #include \"a.glsl\""), "{}", error);
+    }
+
+    #[test]
+    fn missing_include() {
+        let preprocessor = preprocessor("missing", &[("common.glsl", "#include \"missing.glsl\"")]);
+        let mut includes = Vec::new();
+        let error = preprocessor.preprocess_tracked("#include \"common.glsl\"", "main.fs", &mut includes).err().unwrap();
+        assert!(error.contains("common.glsl:1: cannot include"), "{}", error);
+        let dir = &preprocessor.include_dir;
+        assert_eq!(includes, [dir.join("common.glsl"), dir.join("missing.glsl")]);
+        assert!(preprocessor.preprocess("#include common.glsl", "main.fs").err().unwrap().contains("malformed"));
+    }
+
+    #[test]
+    fn errors_refer_to_included_files() {
+        let preprocessor = preprocessor("errors", &[("common.glsl", "float a;\nfoo = 1.0;\n")]);
+        let src = "#version 330 core\n#include \"common.glsl\"\nvoid main() {}\n";
+        let out = preprocessor.preprocess(src, "main.fs").unwrap();
+        // As Mesa reports an error on line 2 of string 1
+        let error = ShaderError::compile(gl::FRAGMENT_SHADER, &out.code, &out.files, "1:2(1): error: `foo' undeclared".to_owned());
+        let diagnostic = &error.diagnostics()[0];
+        assert_eq!(diagnostic.file.as_deref(), Some(preprocessor.include_dir.join("common.glsl").as_path()));
+        assert!(error.to_string().contains("common.glsl:2:1: error: `foo' undeclared\n  |\n2 | foo = 1.0;\n  | ^"),
+                "{}", error);
+    }
+}
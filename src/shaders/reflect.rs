@@ -0,0 +1,425 @@
+use std::ffi::CString;
+
+use gl::{self, types::*};
+
+use super::ShaderProgram;
+use crate::context;
+use crate::vao::{AttributeKind, VertexAttribPointer};
+
+/// An active attribute or fragment output of a program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActiveVariable {
+    pub name: String,
+    /// GL type, e.g. `gl::FLOAT_VEC3`.
+    pub ty: GLenum,
+    /// Number of array elements, 1 for non-arrays.
+    pub size: GLint,
+    /// -1 for built-in variables.
+    pub location: GLint,
+}
+
+/// An active uniform of a program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActiveUniform {
+    /// Name of the uniform, `name[0]` for arrays.
+    pub name: String,
+    /// GL type, e.g. `gl::FLOAT_MAT4` or `gl::SAMPLER_2D`.
+    pub ty: GLenum,
+    /// Number of array elements, 1 for non-arrays.
+    pub size: GLint,
+    /// -1 for uniforms of a uniform block.
+    pub location: GLint,
+    /// Index of the uniform block the uniform belongs to, if any.
+    pub block_index: Option<GLuint>,
+}
+
+/// An active uniform block of a program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UniformBlock {
+    pub name: String,
+    pub index: GLuint,
+    /// Uniform buffer binding point the block reads from.
+    pub binding: GLuint,
+    /// Size of the buffer storage needed by the block, in bytes.
+    pub data_size: GLint,
+    /// Indices of the block uniforms in `ShaderProgram::uniforms`.
+    pub uniform_indices: Vec<GLuint>,
+}
+
+impl ShaderProgram {
+    fn get(&self, pname: GLenum) -> GLint {
+        let mut value = 0;
+        unsafe {
+            gl::GetProgramiv(self.id, pname, &mut value);
+        }
+        value
+    }
+
+    /// The active vertex attributes, including built-ins such as `gl_VertexID`.
+    ///
+    /// See [glGetActiveAttrib](https://docs.gl/gl3/glGetActiveAttrib) for details.
+    pub fn attributes(&self) -> Vec<ActiveVariable> {
+        let max_len = self.get(gl::ACTIVE_ATTRIBUTE_MAX_LENGTH);
+        (0..self.get(gl::ACTIVE_ATTRIBUTES) as GLuint)
+            .map(|i| {
+                let (name, ty, size) = active_resource(self.id, i, max_len, gl::GetActiveAttrib);
+                let location = match CString::new(name.as_str()) {
+                    Ok(c_name) => unsafe { gl::GetAttribLocation(self.id, c_name.as_ptr()) },
+                    Err(_) => -1,
+                };
+                ActiveVariable { name, ty, size, location }
+            })
+            .collect()
+    }
+
+    /// The active uniforms, including those of uniform blocks.
+    ///
+    /// See [glGetActiveUniform](https://docs.gl/gl3/glGetActiveUniform) for details.
+    pub fn uniforms(&self) -> Vec<ActiveUniform> {
+        let max_len = self.get(gl::ACTIVE_UNIFORM_MAX_LENGTH);
+        (0..self.get(gl::ACTIVE_UNIFORMS) as GLuint)
+            .map(|i| {
+                let (name, ty, size) = active_resource(self.id, i, max_len, gl::GetActiveUniform);
+                let mut block_index = -1;
+                unsafe {
+                    gl::GetActiveUniformsiv(self.id, 1, &i, gl::UNIFORM_BLOCK_INDEX, &mut block_index);
+                }
+                ActiveUniform {
+                    location: self.uniform_location(&name),
+                    name,
+                    ty,
+                    size,
+                    block_index: if block_index < 0 { None } else { Some(block_index as GLuint) },
+                }
+            })
+            .collect()
+    }
+
+    /// The active uniform blocks.
+    ///
+    /// See [glGetActiveUniformBlock](https://docs.gl/gl3/glGetActiveUniformBlock) for details.
+    pub fn uniform_blocks(&self) -> Vec<UniformBlock> {
+        let max_len = self.get(gl::ACTIVE_UNIFORM_BLOCK_MAX_NAME_LENGTH);
+        (0..self.get(gl::ACTIVE_UNIFORM_BLOCKS) as GLuint)
+            .map(|index| unsafe {
+                let get = |pname| {
+                    let mut value = 0;
+                    gl::GetActiveUniformBlockiv(self.id, index, pname, &mut value);
+                    value
+                };
+                let mut name = vec![0u8; max_len.max(1) as usize];
+                let mut len = 0;
+                gl::GetActiveUniformBlockName(self.id, index, name.len() as GLsizei, &mut len,
+                                              name.as_mut_ptr() as *mut GLchar);
+                name.truncate(len.max(0) as usize);
+
+                let mut uniform_indices = vec![0; get(gl::UNIFORM_BLOCK_ACTIVE_UNIFORMS) as usize];
+                if !uniform_indices.is_empty() {
+                    gl::GetActiveUniformBlockiv(self.id, index, gl::UNIFORM_BLOCK_ACTIVE_UNIFORM_INDICES,
+                                                uniform_indices.as_mut_ptr());
+                }
+                UniformBlock {
+                    name: String::from_utf8_lossy(&name).into_owned(),
+                    index,
+                    binding: get(gl::UNIFORM_BLOCK_BINDING) as GLuint,
+                    data_size: get(gl::UNIFORM_BLOCK_DATA_SIZE),
+                    uniform_indices: uniform_indices.into_iter().map(|i| i as GLuint).collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// The active fragment shader outputs, or `None` if the context does not support program
+    /// interface queries (OpenGL 4.3 or `GL_ARB_program_interface_query`).
+    ///
+    /// See [glGetProgramResource](https://docs.gl/gl4/glGetProgramResource) for details.
+    pub fn outputs(&self) -> Option<Vec<ActiveVariable>> {
+        if !gl::GetProgramInterfaceiv::is_loaded()
+            || !context::has_feature((4, 3), "GL_ARB_program_interface_query")
+        {
+            return None;
+        }
+        unsafe {
+            let (mut count, mut max_len) = (0, 0);
+            gl::GetProgramInterfaceiv(self.id, gl::PROGRAM_OUTPUT, gl::ACTIVE_RESOURCES, &mut count);
+            gl::GetProgramInterfaceiv(self.id, gl::PROGRAM_OUTPUT, gl::MAX_NAME_LENGTH, &mut max_len);
+            let outputs = (0..count as GLuint)
+                .map(|i| {
+                    let mut name = vec![0u8; max_len.max(1) as usize];
+                    let mut len = 0;
+                    gl::GetProgramResourceName(self.id, gl::PROGRAM_OUTPUT, i, name.len() as GLsizei,
+                                               &mut len, name.as_mut_ptr() as *mut GLchar);
+                    name.truncate(len.max(0) as usize);
+
+                    let props = [gl::TYPE, gl::ARRAY_SIZE, gl::LOCATION];
+                    let mut values = [0; 3];
+                    gl::GetProgramResourceiv(self.id, gl::PROGRAM_OUTPUT, i, props.len() as GLsizei,
+                                             props.as_ptr(), values.len() as GLsizei, std::ptr::null_mut(),
+                                             values.as_mut_ptr());
+                    ActiveVariable {
+                        name: String::from_utf8_lossy(&name).into_owned(),
+                        ty: values[0] as GLenum,
+                        size: values[1].max(1),
+                        location: values[2],
+                    }
+                })
+                .collect();
+            Some(outputs)
+        }
+    }
+
+    /// Check that `attribs`, e.g. the pointers given to `vao::create`, feed every active vertex
+    /// attribute of the program.
+    ///
+    /// A pointer may have fewer components than its attribute, since GL fills the missing ones
+    /// from (0, 0, 0, 1), but not more, except for packed types such as
+    /// `gl::INT_2_10_10_10_REV` whose size is always 4. The `kind` of the pointer must match the
+    /// base type of the attribute, e.g. `AttributeKind::Integer` for `ivec2`. Returns the list of
+    /// mismatches on error.
+    pub fn check_vertex_attribs(&self, attribs: &[VertexAttribPointer]) -> Result<(), String> {
+        let mut errors = Vec::new();
+        for attr in self.attributes().iter().filter(|a| a.location >= 0) {
+            let (components, locations) = attribute_shape(attr.ty);
+            let kind = attribute_kind(attr.ty);
+            for location in attr.location..attr.location + locations * attr.size {
+                match attribs.iter().find(|p| p.index == location as GLuint) {
+                    None => errors.push(format!("no vertex attribute pointer for {} {} at location {}",
+                                                type_name(attr.ty), attr.name, location)),
+                    Some(p) if p.size > components && !is_packed(p.ty) => {
+                        errors.push(format!("{} {} at location {} has {} components, its pointer has {}",
+                                            type_name(attr.ty), attr.name, location, components, p.size))
+                    }
+                    Some(p) if p.kind != kind => {
+                        errors.push(format!("{} {} at location {} reads {:?} values, its pointer gives {:?}",
+                                            type_name(attr.ty), attr.name, location, kind, p.kind))
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    /// Check that every sampler uniform of the program reads from a texture unit with a texture
+    /// of the matching target bound, e.g. right before a draw call.
+    ///
+    /// Returns the list of unbound samplers on error.
+    pub fn check_samplers(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        unsafe {
+            let mut active_texture = 0;
+            gl::GetIntegerv(gl::ACTIVE_TEXTURE, &mut active_texture);
+            for uniform in self.uniforms().iter().filter(|u| u.location >= 0) {
+                let binding = match sampler_binding(uniform.ty) {
+                    Some(binding) => binding,
+                    None => continue,
+                };
+                let base_name = uniform.name.trim_end_matches("[0]");
+                for element in 0..uniform.size {
+                    let name = if uniform.size > 1 {
+                        format!("{}[{}]", base_name, element)
+                    } else {
+                        base_name.to_owned()
+                    };
+                    let mut unit = 0;
+                    gl::GetUniformiv(self.id, self.uniform_location(&name), &mut unit);
+                    gl::ActiveTexture(gl::TEXTURE0 + unit as GLuint);
+                    let mut texture = 0;
+                    gl::GetIntegerv(binding, &mut texture);
+                    if texture == 0 {
+                        errors.push(format!("{} {} reads texture unit {}, which has no texture bound to its target",
+                                            type_name(uniform.ty), name, unit));
+                    }
+                }
+            }
+            gl::ActiveTexture(active_texture as GLenum);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+}
+
+/// Name, type and array size of an active attribute or uniform, given `glGetActiveAttrib` or
+/// `glGetActiveUniform`.
+fn active_resource(
+    program: GLuint,
+    index: GLuint,
+    max_len: GLint,
+    get_active: unsafe fn(GLuint, GLuint, GLsizei, *mut GLsizei, *mut GLint, *mut GLenum, *mut GLchar),
+) -> (String, GLenum, GLint) {
+    let mut name = vec![0u8; max_len.max(1) as usize];
+    let (mut len, mut size, mut ty) = (0, 0, 0);
+    unsafe {
+        get_active(program, index, name.len() as GLsizei, &mut len, &mut size, &mut ty,
+                   name.as_mut_ptr() as *mut GLchar);
+    }
+    name.truncate(len.max(0) as usize);
+    (String::from_utf8_lossy(&name).into_owned(), ty, size)
+}
+
+/// Number of components per location and number of locations taken by a vertex attribute type.
+fn attribute_shape(ty: GLenum) -> (GLint, GLint) {
+    match ty {
+        gl::FLOAT | gl::INT | gl::UNSIGNED_INT | gl::DOUBLE | gl::BOOL => (1, 1),
+        gl::FLOAT_VEC2 | gl::INT_VEC2 | gl::UNSIGNED_INT_VEC2 | gl::DOUBLE_VEC2 | gl::BOOL_VEC2 => (2, 1),
+        gl::FLOAT_VEC3 | gl::INT_VEC3 | gl::UNSIGNED_INT_VEC3 | gl::DOUBLE_VEC3 | gl::BOOL_VEC3 => (3, 1),
+        gl::FLOAT_VEC4 | gl::INT_VEC4 | gl::UNSIGNED_INT_VEC4 | gl::DOUBLE_VEC4 | gl::BOOL_VEC4 => (4, 1),
+        // matCxR takes a location per column
+        gl::FLOAT_MAT2 | gl::DOUBLE_MAT2 => (2, 2),
+        gl::FLOAT_MAT2x3 | gl::DOUBLE_MAT2x3 => (3, 2),
+        gl::FLOAT_MAT2x4 | gl::DOUBLE_MAT2x4 => (4, 2),
+        gl::FLOAT_MAT3x2 | gl::DOUBLE_MAT3x2 => (2, 3),
+        gl::FLOAT_MAT3 | gl::DOUBLE_MAT3 => (3, 3),
+        gl::FLOAT_MAT3x4 | gl::DOUBLE_MAT3x4 => (4, 3),
+        gl::FLOAT_MAT4x2 | gl::DOUBLE_MAT4x2 => (2, 4),
+        gl::FLOAT_MAT4x3 | gl::DOUBLE_MAT4x3 => (3, 4),
+        gl::FLOAT_MAT4 | gl::DOUBLE_MAT4 => (4, 4),
+        _ => (4, 1),
+    }
+}
+
+/// How a vertex attribute of type `ty` must be read, e.g. `AttributeKind::Integer` for `ivec2`.
+fn attribute_kind(ty: GLenum) -> AttributeKind {
+    match ty {
+        gl::INT | gl::INT_VEC2 | gl::INT_VEC3 | gl::INT_VEC4
+        | gl::UNSIGNED_INT | gl::UNSIGNED_INT_VEC2 | gl::UNSIGNED_INT_VEC3 | gl::UNSIGNED_INT_VEC4 => {
+            AttributeKind::Integer
+        }
+        gl::DOUBLE | gl::DOUBLE_VEC2 | gl::DOUBLE_VEC3 | gl::DOUBLE_VEC4
+        | gl::DOUBLE_MAT2 | gl::DOUBLE_MAT3 | gl::DOUBLE_MAT4
+        | gl::DOUBLE_MAT2x3 | gl::DOUBLE_MAT2x4 | gl::DOUBLE_MAT3x2
+        | gl::DOUBLE_MAT3x4 | gl::DOUBLE_MAT4x2 | gl::DOUBLE_MAT4x3 => AttributeKind::Double,
+        _ => AttributeKind::Float,
+    }
+}
+
+/// Whether `ty` packs all the components of a vertex in a single value, so that the size of the
+/// pointer is fixed whatever the attribute it feeds.
+fn is_packed(ty: GLenum) -> bool {
+    matches!(ty, gl::INT_2_10_10_10_REV | gl::UNSIGNED_INT_2_10_10_10_REV | gl::UNSIGNED_INT_10F_11F_11F_REV)
+}
+
+/// The `glGetIntegerv` parameter giving the texture bound to the target a sampler type reads
+/// from, or `None` if `ty` is not a sampler type.
+fn sampler_binding(ty: GLenum) -> Option<GLenum> {
+    let binding = match ty {
+        gl::SAMPLER_1D | gl::SAMPLER_1D_SHADOW | gl::INT_SAMPLER_1D | gl::UNSIGNED_INT_SAMPLER_1D => {
+            gl::TEXTURE_BINDING_1D
+        }
+        gl::SAMPLER_2D | gl::SAMPLER_2D_SHADOW | gl::INT_SAMPLER_2D | gl::UNSIGNED_INT_SAMPLER_2D => {
+            gl::TEXTURE_BINDING_2D
+        }
+        gl::SAMPLER_3D | gl::INT_SAMPLER_3D | gl::UNSIGNED_INT_SAMPLER_3D => gl::TEXTURE_BINDING_3D,
+        gl::SAMPLER_CUBE | gl::SAMPLER_CUBE_SHADOW | gl::INT_SAMPLER_CUBE | gl::UNSIGNED_INT_SAMPLER_CUBE => {
+            gl::TEXTURE_BINDING_CUBE_MAP
+        }
+        gl::SAMPLER_1D_ARRAY | gl::SAMPLER_1D_ARRAY_SHADOW | gl::INT_SAMPLER_1D_ARRAY
+        | gl::UNSIGNED_INT_SAMPLER_1D_ARRAY => gl::TEXTURE_BINDING_1D_ARRAY,
+        gl::SAMPLER_2D_ARRAY | gl::SAMPLER_2D_ARRAY_SHADOW | gl::INT_SAMPLER_2D_ARRAY
+        | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY => gl::TEXTURE_BINDING_2D_ARRAY,
+        gl::SAMPLER_CUBE_MAP_ARRAY | gl::SAMPLER_CUBE_MAP_ARRAY_SHADOW | gl::INT_SAMPLER_CUBE_MAP_ARRAY
+        | gl::UNSIGNED_INT_SAMPLER_CUBE_MAP_ARRAY => gl::TEXTURE_BINDING_CUBE_MAP_ARRAY,
+        gl::SAMPLER_2D_RECT | gl::SAMPLER_2D_RECT_SHADOW | gl::INT_SAMPLER_2D_RECT
+        | gl::UNSIGNED_INT_SAMPLER_2D_RECT => gl::TEXTURE_BINDING_RECTANGLE,
+        gl::SAMPLER_BUFFER | gl::INT_SAMPLER_BUFFER | gl::UNSIGNED_INT_SAMPLER_BUFFER => gl::TEXTURE_BINDING_BUFFER,
+        gl::SAMPLER_2D_MULTISAMPLE | gl::INT_SAMPLER_2D_MULTISAMPLE | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE => {
+            gl::TEXTURE_BINDING_2D_MULTISAMPLE
+        }
+        gl::SAMPLER_2D_MULTISAMPLE_ARRAY | gl::INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+        | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY => gl::TEXTURE_BINDING_2D_MULTISAMPLE_ARRAY,
+        _ => return None,
+    };
+    Some(binding)
+}
+
+/// GLSL name of a GL type, e.g. `vec3` for `gl::FLOAT_VEC3`.
+pub fn type_name(ty: GLenum) -> &'static str {
+    match ty {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::DOUBLE => "double",
+        gl::DOUBLE_VEC2 => "dvec2",
+        gl::DOUBLE_VEC3 => "dvec3",
+        gl::DOUBLE_VEC4 => "dvec4",
+        gl::INT => "int",
+        gl::INT_VEC2 => "ivec2",
+        gl::INT_VEC3 => "ivec3",
+        gl::INT_VEC4 => "ivec4",
+        gl::UNSIGNED_INT => "uint",
+        gl::UNSIGNED_INT_VEC2 => "uvec2",
+        gl::UNSIGNED_INT_VEC3 => "uvec3",
+        gl::UNSIGNED_INT_VEC4 => "uvec4",
+        gl::BOOL => "bool",
+        gl::BOOL_VEC2 => "bvec2",
+        gl::BOOL_VEC3 => "bvec3",
+        gl::BOOL_VEC4 => "bvec4",
+        gl::FLOAT_MAT2 => "mat2",
+        gl::FLOAT_MAT3 => "mat3",
+        gl::FLOAT_MAT4 => "mat4",
+        gl::FLOAT_MAT2x3 => "mat2x3",
+        gl::FLOAT_MAT2x4 => "mat2x4",
+        gl::FLOAT_MAT3x2 => "mat3x2",
+        gl::FLOAT_MAT3x4 => "mat3x4",
+        gl::FLOAT_MAT4x2 => "mat4x2",
+        gl::FLOAT_MAT4x3 => "mat4x3",
+        gl::DOUBLE_MAT2 => "dmat2",
+        gl::DOUBLE_MAT3 => "dmat3",
+        gl::DOUBLE_MAT4 => "dmat4",
+        gl::DOUBLE_MAT2x3 => "dmat2x3",
+        gl::DOUBLE_MAT2x4 => "dmat2x4",
+        gl::DOUBLE_MAT3x2 => "dmat3x2",
+        gl::DOUBLE_MAT3x4 => "dmat3x4",
+        gl::DOUBLE_MAT4x2 => "dmat4x2",
+        gl::DOUBLE_MAT4x3 => "dmat4x3",
+        gl::SAMPLER_1D => "sampler1D",
+        gl::SAMPLER_2D => "sampler2D",
+        gl::SAMPLER_3D => "sampler3D",
+        gl::SAMPLER_CUBE => "samplerCube",
+        gl::SAMPLER_1D_SHADOW => "sampler1DShadow",
+        gl::SAMPLER_2D_SHADOW => "sampler2DShadow",
+        gl::SAMPLER_CUBE_SHADOW => "samplerCubeShadow",
+        gl::SAMPLER_1D_ARRAY => "sampler1DArray",
+        gl::SAMPLER_2D_ARRAY => "sampler2DArray",
+        gl::SAMPLER_1D_ARRAY_SHADOW => "sampler1DArrayShadow",
+        gl::SAMPLER_2D_ARRAY_SHADOW => "sampler2DArrayShadow",
+        gl::SAMPLER_CUBE_MAP_ARRAY => "samplerCubeArray",
+        gl::SAMPLER_CUBE_MAP_ARRAY_SHADOW => "samplerCubeArrayShadow",
+        gl::SAMPLER_2D_RECT => "sampler2DRect",
+        gl::SAMPLER_2D_RECT_SHADOW => "sampler2DRectShadow",
+        gl::SAMPLER_BUFFER => "samplerBuffer",
+        gl::SAMPLER_2D_MULTISAMPLE => "sampler2DMS",
+        gl::SAMPLER_2D_MULTISAMPLE_ARRAY => "sampler2DMSArray",
+        gl::INT_SAMPLER_1D => "isampler1D",
+        gl::INT_SAMPLER_2D => "isampler2D",
+        gl::INT_SAMPLER_3D => "isampler3D",
+        gl::INT_SAMPLER_CUBE => "isamplerCube",
+        gl::INT_SAMPLER_1D_ARRAY => "isampler1DArray",
+        gl::INT_SAMPLER_2D_ARRAY => "isampler2DArray",
+        gl::INT_SAMPLER_CUBE_MAP_ARRAY => "isamplerCubeArray",
+        gl::INT_SAMPLER_2D_RECT => "isampler2DRect",
+        gl::INT_SAMPLER_BUFFER => "isamplerBuffer",
+        gl::INT_SAMPLER_2D_MULTISAMPLE => "isampler2DMS",
+        gl::INT_SAMPLER_2D_MULTISAMPLE_ARRAY => "isampler2DMSArray",
+        gl::UNSIGNED_INT_SAMPLER_1D => "usampler1D",
+        gl::UNSIGNED_INT_SAMPLER_2D => "usampler2D",
+        gl::UNSIGNED_INT_SAMPLER_3D => "usampler3D",
+        gl::UNSIGNED_INT_SAMPLER_CUBE => "usamplerCube",
+        gl::UNSIGNED_INT_SAMPLER_1D_ARRAY => "usampler1DArray",
+        gl::UNSIGNED_INT_SAMPLER_2D_ARRAY => "usampler2DArray",
+        gl::UNSIGNED_INT_SAMPLER_CUBE_MAP_ARRAY => "usamplerCubeArray",
+        gl::UNSIGNED_INT_SAMPLER_2D_RECT => "usampler2DRect",
+        gl::UNSIGNED_INT_SAMPLER_BUFFER => "usamplerBuffer",
+        gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE => "usampler2DMS",
+        gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY => "usampler2DMSArray",
+        _ => "unknown",
+    }
+}
@@ -0,0 +1,130 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use gl;
+
+use super::preprocessor::Preprocessor;
+use super::{link, ShaderError, ShaderProgram};
+
+/// A shader program loaded from source files that is rebuilt when they change.
+///
+/// Sources are read at runtime instead of being embedded with `include_str!`, so shaders can be
+/// edited while the app runs. Call `reload_if_modified`, e.g. from `OpenGLApp::update`, to pick
+/// up the changes. Files pulled in with `#include` are watched too.
+#[derive(Default)]
+pub struct ReloadableProgram {
+    preprocessor: Preprocessor,
+    vs_path: PathBuf,
+    fs_path: PathBuf,
+    /// Files the last build read, along with their modification times at that point.
+    sources: Vec<(PathBuf, Option<SystemTime>)>,
+    program: ShaderProgram,
+}
+
+impl ReloadableProgram {
+    /// Build a program from the given vertex and fragment shader files.
+    /// Relative paths are resolved from `SHADER_DIR`.
+    pub fn load<P: AsRef<Path>, Q: AsRef<Path>>(vs_path: P, fs_path: Q) -> Result<Self, ShaderError> {
+        Self::load_with(Preprocessor::default(), vs_path, fs_path)
+    }
+
+    /// Build a program from the given vertex and fragment shader files,
+    /// preprocessed by `preprocessor`. Relative paths are resolved from its `include_dir`.
+    pub fn load_with<P: AsRef<Path>, Q: AsRef<Path>>(
+        preprocessor: Preprocessor,
+        vs_path: P,
+        fs_path: Q,
+    ) -> Result<Self, ShaderError> {
+        let mut reloadable = Self::unbuilt(preprocessor, vs_path, fs_path);
+        reloadable.reload()?;
+        Ok(reloadable)
+    }
+
+    /// Like `load_with`, but a failed first build is only logged: the program stays empty, with
+    /// id 0, until the files are fixed and `reload_if_modified` picks them up.
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(preprocessor: Preprocessor, vs_path: P, fs_path: Q) -> Self {
+        let mut reloadable = Self::unbuilt(preprocessor, vs_path, fs_path);
+        // Already logged
+        let _ = reloadable.reload();
+        reloadable
+    }
+
+    fn unbuilt<P: AsRef<Path>, Q: AsRef<Path>>(preprocessor: Preprocessor, vs_path: P, fs_path: Q) -> Self {
+        Self {
+            vs_path: preprocessor.include_dir.join(vs_path),
+            fs_path: preprocessor.include_dir.join(fs_path),
+            preprocessor,
+            sources: Vec::new(),
+            program: ShaderProgram::default(),
+        }
+    }
+
+    /// The last program successfully built.
+    pub fn program(&self) -> &ShaderProgram {
+        &self.program
+    }
+
+    fn modification_time(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Rebuild the program if a source file changed since the last build.
+    ///
+    /// Returns whether the program was replaced. When the new sources fail to compile or link,
+    /// the error is logged and returned, and the previous program is kept.
+    pub fn reload_if_modified(&mut self) -> Result<bool, ShaderError> {
+        let modified = self
+            .sources
+            .iter()
+            .any(|(path, time)| Self::modification_time(path) != *time);
+        if !modified {
+            return Ok(false);
+        }
+        self.reload().map(|_| true)
+    }
+
+    /// Rebuild the program from its source files, keeping the previous one on failure.
+    pub fn reload(&mut self) -> Result<(), ShaderError> {
+        self.build().inspect_err(|e| {
+            log::error!("Failed to build shader program from {} and {}:\n{}",
+                        self.vs_path.display(), self.fs_path.display(), e);
+        })?;
+        log::info!("Built shader program {} from {} and {}",
+                   self.program.id, self.vs_path.display(), self.fs_path.display());
+        Ok(())
+    }
+
+    fn build(&mut self) -> Result<(), ShaderError> {
+        // Record the times before reading so that a failed build is not retried until the
+        // sources change again, and a change made while building is not missed.
+        // Included files are only known once the main files are preprocessed. Those that could
+        // not be read are watched too, so that creating them triggers a reload.
+        let mut sources: Vec<_> = [&self.vs_path, &self.fs_path]
+            .iter()
+            .map(|path| (path.to_path_buf(), Self::modification_time(path)))
+            .collect();
+
+        let mut includes = Vec::new();
+        let mut preprocess = |path: &Path| {
+            fs::read_to_string(path)
+                .map_err(|e| format!("{}: {}", path.display(), e))
+                .and_then(|src| self.preprocessor.preprocess_tracked(&src, path, &mut includes))
+                .map_err(ShaderError::Preprocess)
+        };
+        let vs_src = preprocess(&self.vs_path);
+        let fs_src = preprocess(&self.fs_path);
+        for file in includes {
+            if !sources.iter().any(|(path, _)| *path == file) {
+                let time = Self::modification_time(&file);
+                sources.push((file, time));
+            }
+        }
+        self.sources = sources;
+
+        let vs = vs_src?.compile(gl::VERTEX_SHADER)?;
+        let fs = fs_src?.compile(gl::FRAGMENT_SHADER)?;
+        self.program = link(&vs, &fs)?;
+        Ok(())
+    }
+}
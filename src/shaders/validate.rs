@@ -0,0 +1,779 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use gl::{self, types::*};
+use naga::front::glsl::{Frontend, Options};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga::{Binding, Handle, Module, ScalarKind, ShaderStage, Span, Type, TypeInner};
+
+use super::preprocessor::{line_locations, PreprocessedSource, Preprocessor};
+use super::{Diagnostic, DiagnosticLevel, ShaderError};
+
+/// Type of the shader in a file, from its extension: `.vs`, `.tcs`, `.tes`, `.gs`, `.fs` or `.cs`,
+/// or their `.vert`, `.tesc`, `.tese`, `.geom`, `.frag` and `.comp` counterparts.
+pub fn shader_type_from_path(path: &Path) -> Option<GLenum> {
+    match path.extension()?.to_str()? {
+        "vs" | "vert" => Some(gl::VERTEX_SHADER),
+        "tcs" | "tesc" => Some(gl::TESS_CONTROL_SHADER),
+        "tes" | "tese" => Some(gl::TESS_EVALUATION_SHADER),
+        "gs" | "geom" => Some(gl::GEOMETRY_SHADER),
+        "fs" | "frag" => Some(gl::FRAGMENT_SHADER),
+        "cs" | "comp" => Some(gl::COMPUTE_SHADER),
+        _ => None,
+    }
+}
+
+/// Outcome of the validation of code without errors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Validation {
+    /// The code was parsed and validated.
+    Complete,
+    /// The code was not validated, as it uses constructs that cannot be rewritten for naga yet,
+    /// e.g. declarations of several variables. They are reported as warnings.
+    Skipped(Vec<Diagnostic>),
+}
+
+/// Parse and validate preprocessed GLSL on the CPU, without an OpenGL context.
+///
+/// The code is checked with naga's GLSL front-end, which follows the GLSL 4.50 rules whatever
+/// the `#version` of the code. Only the `binding` and `location` layout qualifiers and the
+/// double types are checked against that version, other newer features are accepted.
+///
+/// Errors are reported like compile errors, with locations in the original files. Only vertex,
+/// fragment and compute shaders are supported.
+pub fn validate(source: &PreprocessedSource, ty: GLenum) -> Result<Validation, ShaderError> {
+    parse(source, ty).map(|module| match module {
+        Ok(_) => Validation::Complete,
+        Err(warnings) => Validation::Skipped(warnings),
+    })
+}
+
+/// Preprocess and validate the shader file at `path`, see `validate`.
+pub fn validate_file<P: AsRef<Path>>(
+    preprocessor: &Preprocessor,
+    path: P,
+    ty: GLenum,
+) -> Result<Validation, ShaderError> {
+    let source = preprocessor.preprocess_file(path).map_err(ShaderError::Preprocess)?;
+    validate(&source, ty)
+}
+
+/// Validate a vertex shader and a fragment shader, and check that the vertex shader has an
+/// output of the same type for every fragment shader input, as linking them would.
+///
+/// The interfaces are not checked if one of the shaders is skipped.
+pub fn validate_program<P: AsRef<Path>, Q: AsRef<Path>>(
+    preprocessor: &Preprocessor,
+    vs_path: P,
+    fs_path: Q,
+) -> Result<Validation, ShaderError> {
+    let preprocess = |path: &Path| preprocessor.preprocess_file(path).map_err(ShaderError::Preprocess);
+    let vs = parse(&preprocess(vs_path.as_ref())?, gl::VERTEX_SHADER)?;
+    let fs = parse(&preprocess(fs_path.as_ref())?, gl::FRAGMENT_SHADER)?;
+    let (vs, fs) = match (vs, fs) {
+        (Ok(vs), Ok(fs)) => (vs, fs),
+        (vs, fs) => {
+            let warnings = vec![vs.err(), fs.err()].into_iter().flatten().flatten().collect();
+            return Ok(Validation::Skipped(warnings));
+        }
+    };
+
+    let outputs = interface(&vs, false);
+    let mut log = String::new();
+    for (name, ty) in interface(&fs, true) {
+        match outputs.iter().find(|(output, _)| *output == name) {
+            None => log.push_str(&format!("error: fragment shader input `{}' is not written by the vertex shader\n", name)),
+            Some((_, output_ty)) if *output_ty != ty => log.push_str(&format!(
+                "error: vertex shader output `{}' declared as type `{}', but fragment shader input declared as type `{}'\n",
+                name, output_ty, ty
+            )),
+            Some(_) => {}
+        }
+    }
+    if log.is_empty() {
+        Ok(Validation::Complete)
+    } else {
+        Err(ShaderError::link(log))
+    }
+}
+
+/// Validate every shader file of the preprocessor's `include_dir`, recognized by their
+/// extension (see `shader_type_from_path`), e.g. from a test:
+///
+/// ```no_run
+/// # use learnopengl_rs::shaders::{preprocessor::Preprocessor, validate};
+/// validate::validate_all(&Preprocessor::default()).unwrap();
+/// ```
+///
+/// Shaders of stages naga does not support are skipped, as are those `validate` skips, which are
+/// logged as warnings. Returns the number of shaders validated, or the errors of all the invalid
+/// ones.
+pub fn validate_all(preprocessor: &Preprocessor) -> Result<usize, String> {
+    let dir = &preprocessor.include_dir;
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| format!("{}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    paths.sort();
+
+    let mut count = 0;
+    let mut errors = Vec::new();
+    for path in paths {
+        let ty = match shader_type_from_path(&path) {
+            Some(ty) if naga_stage(ty).is_some() => ty,
+            _ => continue,
+        };
+        // Paths read from `include_dir` are already resolved from it
+        let name = path.file_name().unwrap();
+        match validate_file(preprocessor, name, ty) {
+            Ok(Validation::Complete) => count += 1,
+            Ok(Validation::Skipped(warnings)) => {
+                for warning in warnings {
+                    log::warn!("{}", warning);
+                }
+            }
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+    if errors.is_empty() {
+        Ok(count)
+    } else {
+        Err(errors.join("\n\n"))
+    }
+}
+
+fn naga_stage(ty: GLenum) -> Option<ShaderStage> {
+    match ty {
+        gl::VERTEX_SHADER => Some(ShaderStage::Vertex),
+        gl::FRAGMENT_SHADER => Some(ShaderStage::Fragment),
+        gl::COMPUTE_SHADER => Some(ShaderStage::Compute),
+        _ => None,
+    }
+}
+
+/// Parse and validate `source` into a module, or return the warnings about the constructs that
+/// prevent it.
+fn parse(source: &PreprocessedSource, ty: GLenum) -> Result<Result<Module, Vec<Diagnostic>>, ShaderError> {
+    let stage = naga_stage(ty).ok_or(ShaderError::UnsupportedStage { shader_type: ty })?;
+    let compile_error = |log: String| ShaderError::compile(ty, &source.code, &source.files, log);
+    if !source.code.lines().any(|line| line.trim_start().starts_with("#version")) {
+        return Err(compile_error("error: missing #version directive".to_owned()));
+    }
+    let original_location = |line_number: usize| {
+        line_locations(&source.code).nth(line_number - 1).and_then(|(location, _)| location)
+    };
+
+    let errors = version_errors(&source.code, ty);
+    if !errors.is_empty() {
+        let log = errors.iter().map(|(line_number, column, message)| match original_location(*line_number) {
+            Some((string, line)) => format!("{}:{}({}): error: {}", string, line, column, message),
+            None => format!("error: {}", message),
+        });
+        return Err(compile_error(log.collect::<Vec<_>>().join("\n")));
+    }
+    let unsupported = unsupported_declarations(&source.code);
+    if !unsupported.is_empty() {
+        let warnings = unsupported.into_iter().map(|(line_number, column, message)| {
+            let location = original_location(line_number);
+            Diagnostic {
+                level: DiagnosticLevel::Warning,
+                source_string: location.map(|(string, _)| string),
+                file: location.and_then(|(string, _)| source.files.get(string)).cloned(),
+                line: location.map(|(_, line)| line),
+                column: Some(column),
+                message,
+            }
+        });
+        return Ok(Err(warnings.collect()));
+    }
+
+    let (code, modified_lines) = adapt(&source.code);
+    // Report errors in the log format of Mesa, which columns are only given for lines that were
+    // not rewritten.
+    let log_line = |span: Option<Span>, message: &str| match span.filter(|span| span.is_defined()) {
+        Some(span) => {
+            let location = span.location(&code);
+            let line_number = location.line_number as usize;
+            match original_location(line_number) {
+                Some((string, line)) if modified_lines.contains(&line_number) => {
+                    format!("ERROR: {}:{}: {}", string, line, message)
+                }
+                Some((string, line)) => format!("{}:{}({}): error: {}", string, line, location.line_position, message),
+                None => format!("error: {}", message),
+            }
+        }
+        None => format!("error: {}", message),
+    };
+
+    let module = Frontend::default().parse(&Options::from(stage), &code).map_err(|e| {
+        let log = e.errors.iter().map(|e| log_line(Some(e.meta), &e.kind.to_string())).collect::<Vec<_>>();
+        compile_error(log.join("\n"))
+    })?;
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|e| {
+            // The innermost error is the most specific, as is the last span
+            let mut message = e.to_string();
+            let mut inner: Option<&dyn Error> = e.as_inner().source();
+            while let Some(error) = inner {
+                message = error.to_string();
+                inner = error.source();
+            }
+            let span = e.spans().last().map(|(span, _)| *span);
+            compile_error(log_line(span, &message))
+        })?;
+    Ok(Ok(module))
+}
+
+/// Version of the desktop GLSL the code is written for, e.g. 330, or `None` for GLSL ES.
+fn glsl_version(code: &str) -> Option<u32> {
+    let args = code.lines().find_map(|line| line.trim_start().strip_prefix("#version"))?;
+    let mut words = args.split_whitespace();
+    let version = words.next()?.parse().ok()?;
+    if words.next() == Some("es") {
+        None
+    } else {
+        Some(version)
+    }
+}
+
+/// The lines of `code` without their comments.
+fn code_lines(code: &str) -> Vec<&str> {
+    code.lines().map(|line| line.split("//").next().unwrap()).collect()
+}
+
+/// The global `uniform`, `in` and `out` declarations of `lines`, along with the index of their
+/// line.
+fn global_declarations<'a>(lines: &[&'a str]) -> Vec<(usize, Declaration<'a>)> {
+    let mut declarations = Vec::new();
+    let mut depth = 0;
+    for (i, text) in lines.iter().enumerate() {
+        if depth == 0 {
+            declarations.extend(parse_declaration(text).map(|decl| (i, decl)));
+        }
+        depth += text.matches('{').count() as i32 - text.matches('}').count() as i32;
+    }
+    declarations
+}
+
+/// Find the features newer than the GLSL version of the code that naga would accept: the
+/// `binding` and `location` layout qualifiers, and the double types.
+///
+/// Returns the line numbers, columns and messages of the errors.
+fn version_errors(code: &str, ty: GLenum) -> Vec<(usize, usize, String)> {
+    let version = match glsl_version(code) {
+        Some(version) => version,
+        None => return Vec::new(),
+    };
+    let lines = code_lines(code);
+    let column = |i: usize, word: &str| lines[i].find(word).map_or(1, |start| start + 1);
+    let mut errors = Vec::new();
+
+    for (i, decl) in global_declarations(&lines) {
+        let layout_args = decl.layout.into_iter().flat_map(|layout| layout.split(','));
+        for key in layout_args.filter_map(|arg| Some(arg.split('=').next()?.trim())) {
+            let required = match (key, decl.storage) {
+                ("binding", _) => 420,
+                ("location", "uniform") => 430,
+                ("location", "in") if ty != gl::VERTEX_SHADER => 410,
+                ("location", "out") if ty != gl::FRAGMENT_SHADER => 410,
+                _ => continue,
+            };
+            if version < required {
+                let message = format!("`{}' layout qualifier on {} {} requires GLSL {:.2}",
+                                      key, shader_stage_name(ty), storage_name(decl.storage), required as f32 / 100.0);
+                errors.push((i + 1, column(i, key), message));
+            }
+        }
+    }
+
+    if version < 400 {
+        for (i, text) in lines.iter().enumerate() {
+            let words = text.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'));
+            for word in words.filter(|word| is_double_type(word)) {
+                errors.push((i + 1, column(i, word), format!("`{}' requires GLSL 4.00", word)));
+            }
+        }
+        errors.sort();
+    }
+    errors
+}
+
+/// Find the valid global declarations that `adapt` cannot rewrite yet: those of several
+/// variables, with an initializer or over several lines, and those sharing a line.
+///
+/// Returns the line numbers, columns and messages of the warnings.
+fn unsupported_declarations(code: &str) -> Vec<(usize, usize, String)> {
+    let lines = code_lines(code);
+    let mut warnings = Vec::new();
+    for (i, decl) in global_declarations(&lines) {
+        // A block name may be followed by its brace on the next line, a type may not
+        let next_line = lines[i + 1..].iter().find(|line| !line.trim().is_empty());
+        if decl.rest.contains('{') || (decl.is_block() && next_line.is_some_and(|line| line.trim_start().starts_with('{'))) {
+            continue;
+        }
+        let unsupported = match decl.rest.split_once(';') {
+            None => "declarations over several lines",
+            Some((_, after)) if !after.trim().is_empty() => "several declarations on one line",
+            Some((declarators, _)) if declarators.contains('=') => "initializers of global variables",
+            Some((declarators, _)) if declarators.contains(',') => "declarations of several variables",
+            _ => continue,
+        };
+        let column = lines[i].len() - decl.rest.len() + 1;
+        warnings.push((i + 1, column, format!("{} are not supported yet, the shader was not validated", unsupported)));
+    }
+    warnings
+}
+
+fn shader_stage_name(ty: GLenum) -> &'static str {
+    match ty {
+        gl::VERTEX_SHADER => "vertex shader",
+        gl::FRAGMENT_SHADER => "fragment shader",
+        gl::COMPUTE_SHADER => "compute shader",
+        _ => "shader",
+    }
+}
+
+fn storage_name(storage: &str) -> &'static str {
+    match storage {
+        "in" => "inputs",
+        "out" => "outputs",
+        _ => "uniforms",
+    }
+}
+
+/// Whether `word` is a double precision type, e.g. `double` or `dmat4x3`.
+fn is_double_type(word: &str) -> bool {
+    word == "double"
+        || word.strip_prefix("dvec").is_some_and(|size| matches!(size, "2" | "3" | "4"))
+        || word.strip_prefix('d').and_then(matrix_shape).is_some()
+}
+
+/// Rewrite GLSL written for OpenGL into the Vulkan flavored GLSL 4.50 that naga parses:
+/// - uniforms outside blocks are moved to blocks of their own, and blocks get a binding
+/// - samplers are split into a texture and a sampler, combined where they are used
+/// - inputs and outputs without a location get one
+/// - `#line` directives, which naga does not support, are blanked
+///
+/// Lines are kept in place so that error locations still match. Returns the code along with
+/// the numbers of the lines that were rewritten.
+fn adapt(code: &str) -> (String, HashSet<usize>) {
+    // Inputs and outputs without a location are given the ones following the explicit ones
+    let mut next_location: HashMap<&str, u32> = HashMap::new();
+    for line in code.lines() {
+        if let Some(decl) = parse_declaration(line) {
+            if let Some(location) = decl.location() {
+                let next = next_location.entry(decl.storage).or_insert(0);
+                *next = (*next).max(location + decl.locations());
+            }
+        }
+    }
+
+    let mut samplers: HashMap<String, String> = HashMap::new();
+    let mut binding = 0;
+    let mut depth = 0;
+    let mut modified = HashSet::new();
+    let mut out = String::with_capacity(code.len());
+    for (i, line) in code.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let adapted = if trimmed.starts_with("#version") {
+            Some("#version 450 core".to_owned())
+        } else if trimmed.starts_with("#line") {
+            Some(String::new())
+        } else {
+            match parse_declaration(line) {
+                Some(decl) if depth == 0 => adapt_declaration(&decl, &mut binding, &mut next_location, &mut samplers),
+                _ => replace_identifiers(line, &samplers),
+            }
+        };
+        match adapted {
+            Some(adapted) => {
+                modified.insert(i + 1);
+                out.push_str(&adapted);
+            }
+            None => out.push_str(line),
+        }
+        out.push('\n');
+
+        let code = line.split("//").next().unwrap();
+        depth += code.matches('{').count() as i32 - code.matches('}').count() as i32;
+    }
+    (out, modified)
+}
+
+/// A global `uniform`, `in` or `out` declaration.
+struct Declaration<'a> {
+    /// Arguments of the `layout` qualifier.
+    layout: Option<&'a str>,
+    /// Qualifiers between the layout and the storage qualifier, e.g. `flat`.
+    qualifiers: &'a str,
+    storage: &'a str,
+    /// What follows the storage qualifier, e.g. `vec3 color;` or `Block {`.
+    rest: &'a str,
+}
+
+impl Declaration<'_> {
+    fn location(&self) -> Option<u32> {
+        self.layout?.split(',').find_map(|arg| {
+            let (key, value) = arg.split_once('=')?;
+            if key.trim() == "location" {
+                value.trim().parse().ok()
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Type and name of a variable declaration, e.g. `("vec3", "color")` for `vec3 color;`.
+    fn variable(&self) -> Option<(&str, &str)> {
+        let mut words = self.rest.split(';').next()?.split_whitespace();
+        let ty = words.next()?;
+        let name = words.next()?;
+        if words.next().is_some() || !is_identifier(name) {
+            return None;
+        }
+        Some((ty, name))
+    }
+
+    /// Number of locations the variable takes, one per column for matrices.
+    fn locations(&self) -> u32 {
+        self.variable().and_then(|(ty, _)| matrix_shape(ty)).map_or(1, |(columns, _)| columns)
+    }
+
+    /// Whether the declaration starts a block, e.g. `uniform Camera {`.
+    fn is_block(&self) -> bool {
+        let rest = self.rest.split("//").next().unwrap().trim();
+        rest.contains('{') || (!rest.contains(';') && rest.split_whitespace().count() == 1)
+    }
+}
+
+fn parse_declaration(line: &str) -> Option<Declaration<'_>> {
+    let mut rest = line.trim_start();
+    let mut layout = None;
+    if let Some(after) = rest.strip_prefix("layout") {
+        let after = after.trim_start().strip_prefix('(')?;
+        let end = after.find(')')?;
+        layout = Some(&after[..end]);
+        rest = after[end + 1..].trim_start();
+    }
+    let qualifiers_start = rest;
+    loop {
+        let (word, after) = rest.split_at(rest.find(char::is_whitespace)?);
+        match word {
+            "uniform" | "in" | "out" => {
+                let qualifiers = qualifiers_start[..qualifiers_start.len() - rest.len()].trim_end();
+                return Some(Declaration { layout, qualifiers, storage: word, rest: after.trim_start() });
+            }
+            "flat" | "smooth" | "noperspective" | "centroid" | "sample" | "invariant" | "highp" | "mediump"
+            | "lowp" => rest = after.trim_start(),
+            _ => return None,
+        }
+    }
+}
+
+fn adapt_declaration<'a>(
+    decl: &Declaration<'a>,
+    binding: &mut u32,
+    next_location: &mut HashMap<&'a str, u32>,
+    samplers: &mut HashMap<String, String>,
+) -> Option<String> {
+    let layout = |args: &str| match decl.layout {
+        Some(layout) => format!("layout({}, {})", layout, args),
+        None => format!("layout({})", args),
+    };
+    let qualifiers = if decl.qualifiers.is_empty() { String::new() } else { format!("{} ", decl.qualifiers) };
+
+    if decl.storage != "uniform" {
+        // naga does not support matrix inputs, they are read as vectors, one per column
+        let matrix = decl.variable().and_then(|(ty, name)| Some((ty, name, matrix_shape(ty)?)));
+        if let (Some((ty, name, (columns, rows))), "in") = (matrix, decl.storage) {
+            let location = decl.location().unwrap_or_else(|| {
+                let next = next_location.entry(decl.storage).or_insert(0);
+                *next += columns;
+                *next - columns
+            });
+            let mut adapted = String::new();
+            for column in 0..columns {
+                adapted += &format!("layout(location = {}) {}in vec{} {}_{}; ",
+                                    location + column, qualifiers, rows, name, column);
+            }
+            let columns: Vec<_> = (0..columns).map(|column| format!("{}_{}", name, column)).collect();
+            adapted += &format!("{} {} = {}({});", ty, name, ty, columns.join(", "));
+            return Some(adapted);
+        }
+
+        // Interface blocks and variables with a location are fine as they are
+        if decl.is_block() || decl.location().is_some() {
+            return None;
+        }
+        let next = next_location.entry(decl.storage).or_insert(0);
+        let location = *next;
+        *next += decl.locations();
+        let layout = layout(&format!("location = {}", location));
+        return Some(format!("{} {}{} {}", layout, qualifiers, decl.storage, decl.rest));
+    }
+
+    *binding += 1;
+    if decl.is_block() {
+        let layout = if decl.layout.is_some() {
+            layout(&format!("binding = {}", binding))
+        } else {
+            format!("layout(std140, binding = {})", binding)
+        };
+        return Some(format!("{} {}uniform {}", layout, qualifiers, decl.rest));
+    }
+
+    let mut words = decl.rest.split_whitespace();
+    let ty = words.next()?;
+    let sampler_type = ty.trim_start_matches(['i', 'u']);
+    if sampler_type.starts_with("sampler") {
+        let name = words.next()?.trim_end_matches(';');
+        if name.contains('[') || !is_identifier(name) {
+            // Arrays of samplers are not supported
+            return None;
+        }
+        let prefix = &ty[..ty.len() - sampler_type.len()];
+        let dims = sampler_type.trim_start_matches("sampler").trim_end_matches("Shadow");
+        let sampler = if sampler_type.ends_with("Shadow") { "samplerShadow" } else { "sampler" };
+        samplers.insert(name.to_owned(), format!("{}({}_texture, {}_sampler)", ty, name, name));
+        let texture_binding = *binding;
+        *binding += 1;
+        return Some(format!(
+            "layout(binding = {}) uniform {}texture{} {}_texture; layout(binding = {}) uniform {} {}_sampler;",
+            texture_binding, prefix, dims, name, binding, sampler, name
+        ));
+    }
+
+    Some(format!("layout(std140, binding = {}) uniform _Uniforms{} {{ {} }};", binding, binding, decl.rest))
+}
+
+/// Number of columns and rows of a float matrix type, e.g. `(4, 3)` for `mat4x3`.
+fn matrix_shape(ty: &str) -> Option<(u32, u32)> {
+    let dims = ty.strip_prefix("mat")?;
+    let (columns, rows) = dims.split_once('x').unwrap_or((dims, dims));
+    let (columns, rows) = (columns.parse().ok()?, rows.parse().ok()?);
+    if (2..=4).contains(&columns) && (2..=4).contains(&rows) {
+        Some((columns, rows))
+    } else {
+        None
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Replace the identifiers of `line` found in `replacements`, or return `None` if there is none.
+fn replace_identifiers(line: &str, replacements: &HashMap<String, String>) -> Option<String> {
+    if replacements.is_empty() {
+        return None;
+    }
+    let mut out = String::with_capacity(line.len());
+    let mut replaced = false;
+    let mut rest = line;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        let len = rest[start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len() - start);
+        let word = &rest[start..start + len];
+        // Skip numbers such as `1.0e5` and members such as `a.x`
+        let before = line[..line.len() - rest.len() + start].chars().last();
+        let preceded = before.is_some_and(|c| c.is_ascii_digit() || c == '.');
+        out.push_str(&rest[..start]);
+        match replacements.get(word) {
+            Some(replacement) if !preceded => {
+                out.push_str(replacement);
+                replaced = true;
+            }
+            _ => out.push_str(word),
+        }
+        rest = &rest[start + len..];
+    }
+    out.push_str(rest);
+    if replaced {
+        Some(out)
+    } else {
+        None
+    }
+}
+
+/// Names and types of the user-defined inputs or outputs of the entry point of a module.
+fn interface(module: &Module, inputs: bool) -> Vec<(String, String)> {
+    let entry_point = match module.entry_points.first() {
+        Some(entry_point) => entry_point,
+        None => return Vec::new(),
+    };
+    let mut variables = Vec::new();
+    let mut push = |name: &Option<String>, ty: Handle<Type>, binding: &Option<Binding>| {
+        match (&module.types[ty].inner, binding) {
+            (_, Some(Binding::Location { .. })) => {
+                variables.push((name.clone().unwrap_or_default(), type_name(module, ty)));
+            }
+            (TypeInner::Struct { members, .. }, None) => {
+                for member in members {
+                    if let Some(Binding::Location { .. }) = member.binding {
+                        variables.push((member.name.clone().unwrap_or_default(), type_name(module, member.ty)));
+                    }
+                }
+            }
+            _ => {}
+        }
+    };
+    if inputs {
+        for argument in &entry_point.function.arguments {
+            push(&argument.name, argument.ty, &argument.binding);
+        }
+    } else if let Some(result) = &entry_point.function.result {
+        push(&None, result.ty, &result.binding);
+    }
+    variables
+}
+
+/// GLSL name of a type, e.g. `vec3` or `float[4]`.
+fn type_name(module: &Module, ty: Handle<Type>) -> String {
+    let scalar_prefix = |kind: ScalarKind, width: u8| match (kind, width) {
+        (ScalarKind::Sint, _) => "i",
+        (ScalarKind::Uint, _) => "u",
+        (ScalarKind::Bool, _) => "b",
+        (ScalarKind::Float, 8) => "d",
+        _ => "",
+    };
+    match &module.types[ty].inner {
+        TypeInner::Scalar(scalar) => match (scalar.kind, scalar.width) {
+            (ScalarKind::Sint, _) => "int".to_owned(),
+            (ScalarKind::Uint, _) => "uint".to_owned(),
+            (ScalarKind::Bool, _) => "bool".to_owned(),
+            (ScalarKind::Float, 8) => "double".to_owned(),
+            _ => "float".to_owned(),
+        },
+        TypeInner::Vector { size, scalar } => {
+            format!("{}vec{}", scalar_prefix(scalar.kind, scalar.width), *size as u8)
+        }
+        TypeInner::Matrix { columns, rows, scalar } if *columns as u8 == *rows as u8 => {
+            format!("{}mat{}", scalar_prefix(scalar.kind, scalar.width), *columns as u8)
+        }
+        TypeInner::Matrix { columns, rows, scalar } => {
+            format!("{}mat{}x{}", scalar_prefix(scalar.kind, scalar.width), *columns as u8, *rows as u8)
+        }
+        TypeInner::Array { base, size: naga::ArraySize::Constant(size), .. } => {
+            format!("{}[{}]", type_name(module, *base), size)
+        }
+        other => module.types[ty].name.clone().unwrap_or_else(|| format!("{:?}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate_source(src: &str, ty: GLenum) -> Result<Validation, String> {
+        let source = Preprocessor::default().preprocess(src, "test.glsl").unwrap();
+        validate(&source, ty).map_err(|e| e.to_string())
+    }
+
+    /// `validate_source` with `src` declared as GLSL `version`.
+    fn validate_version(version: u32, src: &str, ty: GLenum) -> Result<Validation, String> {
+        validate_source(&format!("#version {} core\n{}", version, src), ty)
+    }
+
+    fn assert_error(result: Result<Validation, String>, error: &str) {
+        match result {
+            Err(e) => assert!(e.contains(error), "expected {:?} in:\n{}", error, e),
+            Ok(validation) => panic!("expected {:?}, got {:?}", error, validation),
+        }
+    }
+
+    /// Assert that `src` is skipped with a single `warning`.
+    fn assert_skipped(src: &str, warning: &str) {
+        match validate_version(330, src, gl::FRAGMENT_SHADER) {
+            Ok(Validation::Skipped(warnings)) => {
+                assert_eq!(warnings.len(), 1, "{:?}", warnings);
+                assert_eq!(warnings[0].to_string(), warning);
+            }
+            result => panic!("expected {:?}, got {:?}", warning, result),
+        }
+    }
+
+    const SAMPLER_FS: &str = "layout (binding = 1) uniform sampler2D tex;
+out vec4 FragColor;
+void main() {
+    FragColor = texture(tex, vec2(0.0));
+}";
+
+    const LOCATION_VS: &str = "layout (location = 0) in vec3 aPos;
+layout (location = 2) out vec3 color;
+void main() {
+    color = aPos;
+    gl_Position = vec4(aPos, 1.0);
+}";
+
+    const DOUBLE_VS: &str = "in vec3 aPos;
+void main() {
+    dvec3 wide = dvec3(aPos);
+    gl_Position = vec4(vec3(wide), 1.0);
+}";
+
+    #[test]
+    fn newer_features_are_rejected() {
+        assert_error(validate_version(330, SAMPLER_FS, gl::FRAGMENT_SHADER),
+                     "test.glsl:2:9: error: `binding' layout qualifier on fragment shader uniforms requires GLSL 4.20");
+        assert_eq!(validate_version(420, SAMPLER_FS, gl::FRAGMENT_SHADER), Ok(Validation::Complete));
+
+        // Fragment shader outputs and vertex shader inputs have locations since GLSL 3.30
+        assert_error(validate_version(330, LOCATION_VS, gl::VERTEX_SHADER),
+                     "test.glsl:3:9: error: `location' layout qualifier on vertex shader outputs requires GLSL 4.10");
+        assert_eq!(validate_version(410, LOCATION_VS, gl::VERTEX_SHADER), Ok(Validation::Complete));
+        let fs = "layout (location = 0) out vec4 FragColor;\nvoid main() {\n    FragColor = vec4(1.0);\n}";
+        assert_eq!(validate_version(330, fs, gl::FRAGMENT_SHADER), Ok(Validation::Complete));
+
+        assert_error(validate_version(330, DOUBLE_VS, gl::VERTEX_SHADER),
+                     "test.glsl:4:5: error: `dvec3' requires GLSL 4.00");
+        assert_eq!(validate_version(400, DOUBLE_VS, gl::VERTEX_SHADER), Ok(Validation::Complete));
+    }
+
+    #[test]
+    fn unsupported_declarations_are_skipped() {
+        let several = "uniform sampler2D a, b;
+out vec4 FragColor;
+void main() {
+    FragColor = texture(a, vec2(0.0)) + texture(b, vec2(0.0));
+}";
+        assert_skipped(several, "test.glsl:2:9: warning: declarations of several variables are not supported yet, the shader was not validated");
+
+        let multiline = "uniform vec4
+    color;
+out vec4 FragColor;
+void main() {
+    FragColor = color;
+}";
+        assert_skipped(multiline, "test.glsl:2:9: warning: declarations over several lines are not supported yet, the shader was not validated");
+
+        let one_line = "uniform vec4 color; uniform float scale;
+out vec4 FragColor;
+void main() {
+    FragColor = scale * color;
+}";
+        assert_skipped(one_line, "test.glsl:2:9: warning: several declarations on one line are not supported yet, the shader was not validated");
+
+        let initializer = "uniform vec2 offsets[2] = vec2[](vec2(0.0, 1.0), vec2(1.0, 0.0));
+out vec4 FragColor;
+void main() {
+    FragColor = vec4(offsets[0], offsets[1]);
+}";
+        assert_skipped(initializer, "test.glsl:2:9: warning: initializers of global variables are not supported yet, the shader was not validated");
+
+        // A block name may be followed by its brace on the next line
+        let block = "uniform Light
+{
+    vec4 color;
+};
+out vec4 FragColor;
+void main() {
+    FragColor = color;
+}";
+        assert_eq!(validate_version(330, block, gl::FRAGMENT_SHADER), Ok(Validation::Complete));
+    }
+}
@@ -1,8 +1,12 @@
+use std::error::Error;
 use std::ffi::c_void;
-use std::path::Path;
+use std::fmt;
+use std::path::{Path, PathBuf};
 
 use gl::types::*;
-use image::RgbaImage;
+use image::{ColorType, DynamicImage, GenericImageView, ImageError, RgbaImage};
+
+use crate::context::has_feature;
 
 pub struct Texture2d {
     pub id: GLuint,
@@ -11,37 +15,353 @@ pub struct Texture2d {
 
 impl Drop for Texture2d {
     fn drop(&mut self) {
-        println!("Dropping texture {}", self.id);
+        log::debug!("Dropping texture {}", self.id);
         unsafe {
             gl::DeleteTextures(1, std::mem::transmute(&[self.id]));
         }
     }
 }
 
+impl Texture2d {
+    /// Create an empty `DEPTH_COMPONENT24` texture, e.g. to render a shadow map into through a
+    /// framebuffer. Set `params.depth_compare` to sample it with a shadow sampler.
+    pub fn depth(width: u32, height: u32, unit: GLuint, params: &Texture2dParams) -> Result<Self, TextureError> {
+        check_size(width, height)?;
+        let format = PixelFormat {
+            internal: gl::DEPTH_COMPONENT24,
+            format: gl::DEPTH_COMPONENT,
+            ty: gl::UNSIGNED_INT,
+            swizzle: None,
+        };
+        Ok(create(unit, params, width, height, &format, std::ptr::null()))
+    }
+
+    /// Load the image at `path` into a texture, see `from_dynamic_image`.
+    pub fn from_path<P: AsRef<Path>>(path: P, options: &Texture2dOptions) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        let img = image::open(path).map_err(|error| TextureError::Image { path: path.to_owned(), error })?;
+        Self::from_dynamic_image(&img, options)
+    }
+
+    /// Create a texture holding `img`, with an internal format matching its channels and depth:
+    /// `R8`, `RG8`, `RGB8`, `RGBA8` or their 16-bit counterparts.
+    ///
+    /// Grayscale images are swizzled to sample as gray rather than red, with their alpha channel
+    /// if any. sRGB textures only exist with 8-bit color channels, so the other images are
+    /// converted to `SRGB8` or `SRGB8_ALPHA8` when `options.srgb` is set.
+    pub fn from_dynamic_image(img: &DynamicImage, options: &Texture2dOptions) -> Result<Self, TextureError> {
+        let converted;
+        let mut img = match (options.srgb, img.color()) {
+            (true, ColorType::L8 | ColorType::L16 | ColorType::Rgb16) => {
+                converted = DynamicImage::ImageRgb8(img.to_rgb8());
+                &converted
+            }
+            (true, ColorType::La8 | ColorType::La16 | ColorType::Rgba16) => {
+                converted = DynamicImage::ImageRgba8(img.to_rgba8());
+                &converted
+            }
+            _ => img,
+        };
+        let flipped;
+        if options.flip_vertically {
+            flipped = img.flipv();
+            img = &flipped;
+        }
+
+        let color = img.color();
+        let format = pixel_format(color, options.srgb).ok_or(TextureError::UnsupportedFormat(color))?;
+        let (width, height) = img.dimensions();
+        check_size(width, height)?;
+
+        // Rows are tightly packed, e.g. 3 bytes per pixel in RGB images of odd widths, while
+        // OpenGL expects them to start on 4 bytes by default
+        let row_size = width as usize * color.bytes_per_pixel() as usize;
+        let alignment = [8, 4, 2, 1].iter().copied().find(|a| row_size.is_multiple_of(*a)).unwrap_or(1);
+        let mut previous = 0;
+        unsafe {
+            gl::GetIntegerv(gl::UNPACK_ALIGNMENT, &mut previous);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, alignment as GLint);
+        }
+        let texture = create(options.unit, &options.params, width, height, &format, img.as_bytes().as_ptr() as *const c_void);
+        unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, previous);
+        }
+        Ok(texture)
+    }
+}
+
 impl Default for Texture2d {
     fn default() -> Self {
         Self { id: 0, unit: gl::TEXTURE0 }
     }
 }
 
+/// Wrap mode of a texture coordinate outside of [0, 1].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    /// Samples `Texture2dParams::border_color` outside of the texture.
+    ClampToBorder,
+}
+
+impl Wrap {
+    fn gl_mode(self) -> GLint {
+        (match self {
+            Wrap::Repeat => gl::REPEAT,
+            Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Wrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }) as GLint
+    }
+}
+
+/// Filter of a texture sampled smaller than its size, the `Mipmap` ones picking the mipmap
+/// levels closest to the sampled size and blending them if `Linear`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MinFilter {
+    Nearest,
+    Linear,
+    NearestMipmapNearest,
+    LinearMipmapNearest,
+    NearestMipmapLinear,
+    LinearMipmapLinear,
+}
+
+impl MinFilter {
+    /// Whether the filter samples mipmaps, which are then generated when the texture is created.
+    pub fn uses_mipmaps(self) -> bool {
+        !matches!(self, MinFilter::Nearest | MinFilter::Linear)
+    }
+
+    fn gl_filter(self) -> GLint {
+        (match self {
+            MinFilter::Nearest => gl::NEAREST,
+            MinFilter::Linear => gl::LINEAR,
+            MinFilter::NearestMipmapNearest => gl::NEAREST_MIPMAP_NEAREST,
+            MinFilter::LinearMipmapNearest => gl::LINEAR_MIPMAP_NEAREST,
+            MinFilter::NearestMipmapLinear => gl::NEAREST_MIPMAP_LINEAR,
+            MinFilter::LinearMipmapLinear => gl::LINEAR_MIPMAP_LINEAR,
+        }) as GLint
+    }
+}
+
+/// Filter of a texture sampled larger than its size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MagFilter {
+    Nearest,
+    Linear,
+}
+
+impl MagFilter {
+    fn gl_filter(self) -> GLint {
+        (match self {
+            MagFilter::Nearest => gl::NEAREST,
+            MagFilter::Linear => gl::LINEAR,
+        }) as GLint
+    }
+}
+
+/// Comparison of a shadow sampler between the reference depth and the texture, which passes
+/// when `reference <op> texture`, e.g. `LessEqual` to be lit when in front of the occluder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepthCompare {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl DepthCompare {
+    fn gl_func(self) -> GLint {
+        (match self {
+            DepthCompare::Never => gl::NEVER,
+            DepthCompare::Less => gl::LESS,
+            DepthCompare::Equal => gl::EQUAL,
+            DepthCompare::LessEqual => gl::LEQUAL,
+            DepthCompare::Greater => gl::GREATER,
+            DepthCompare::NotEqual => gl::NOTEQUAL,
+            DepthCompare::GreaterEqual => gl::GEQUAL,
+            DepthCompare::Always => gl::ALWAYS,
+        }) as GLint
+    }
+}
+
+// From GL_EXT_texture_filter_anisotropic, core since OpenGL 4.6, which the bindings predate
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
+/// Sampling parameters of a texture.
+///
+/// See [glTexParameter](https://docs.gl/gl3/glTexParameter) for details.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Texture2dParams {
-    pub s_mode: GLint,
-    pub t_mode: GLint,
-    pub min_filter: GLint,
-    pub mag_filter: GLint,
+    pub s_mode: Wrap,
+    pub t_mode: Wrap,
+    pub min_filter: MinFilter,
+    pub mag_filter: MagFilter,
+    /// Offset added to the mipmap level computed for each sample, negative values sharpening the
+    /// texture.
+    pub lod_bias: f32,
+    /// Range of mipmap levels sampled, e.g. to keep away from the smallest ones.
+    pub min_lod: f32,
+    pub max_lod: f32,
+    /// Color sampled outside of the texture with `Wrap::ClampToBorder`.
+    pub border_color: [f32; 4],
+    /// Maximum number of samples taken along the direction of anisotropy, for textures seen at
+    /// grazing angles. 1 disables anisotropic filtering, which also is the case when the driver
+    /// does not support it. Clamped to the maximum of the driver, usually 16.
+    pub max_anisotropy: f32,
+    /// Makes a depth texture, see `Texture2d::depth`, sample as the result of the comparison
+    /// with the reference value given to shadow samplers rather than as the depth.
+    pub depth_compare: Option<DepthCompare>,
 }
 
 impl Default for Texture2dParams {
     fn default() -> Self {
         Self {
-            s_mode: gl::REPEAT as GLint,
-            t_mode: gl::REPEAT as GLint,
-            min_filter: gl::LINEAR as GLint,
-            mag_filter: gl::LINEAR as GLint,
+            s_mode: Wrap::Repeat,
+            t_mode: Wrap::Repeat,
+            min_filter: MinFilter::Linear,
+            mag_filter: MagFilter::Linear,
+            lod_bias: 0.0,
+            min_lod: -1000.0,
+            max_lod: 1000.0,
+            border_color: [0.0; 4],
+            max_anisotropy: 1.0,
+            depth_compare: None,
+        }
+    }
+}
+
+impl Texture2dParams {
+    /// Set the parameters of the texture bound to `GL_TEXTURE_2D`.
+    unsafe fn apply(&self) {
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, self.s_mode.gl_mode());
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, self.t_mode.gl_mode());
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, self.min_filter.gl_filter());
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, self.mag_filter.gl_filter());
+        gl::TexParameterf(gl::TEXTURE_2D, gl::TEXTURE_LOD_BIAS, self.lod_bias);
+        gl::TexParameterf(gl::TEXTURE_2D, gl::TEXTURE_MIN_LOD, self.min_lod);
+        gl::TexParameterf(gl::TEXTURE_2D, gl::TEXTURE_MAX_LOD, self.max_lod);
+        gl::TexParameterfv(gl::TEXTURE_2D, gl::TEXTURE_BORDER_COLOR, self.border_color.as_ptr());
+        if self.max_anisotropy > 1.0 {
+            if has_feature((4, 6), "GL_EXT_texture_filter_anisotropic") {
+                let mut max = 1.0;
+                gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max);
+                gl::TexParameterf(gl::TEXTURE_2D, TEXTURE_MAX_ANISOTROPY, self.max_anisotropy.min(max));
+            } else {
+                log::debug!("Anisotropic filtering is not supported");
+            }
+        }
+        match self.depth_compare {
+            Some(compare) => {
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as GLint);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_FUNC, compare.gl_func());
+            }
+            None => gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_MODE, gl::NONE as GLint),
+        }
+    }
+}
+
+/// How `Texture2d::from_path` and `Texture2d::from_dynamic_image` create a texture.
+pub struct Texture2dOptions {
+    pub unit: GLuint,
+    pub params: Texture2dParams,
+    /// Flip the image vertically, since OpenGL expects the bottom row first while image files
+    /// start with the top one.
+    pub flip_vertically: bool,
+    /// Whether the color channels are sRGB encoded, as they usually are for color textures, so
+    /// that shaders sample linear values. Leave it unset for data such as normal maps.
+    pub srgb: bool,
+}
+
+impl Default for Texture2dOptions {
+    fn default() -> Self {
+        Self {
+            unit: gl::TEXTURE0,
+            params: Texture2dParams::default(),
+            flip_vertically: false,
+            srgb: false,
+        }
+    }
+}
+
+/// Error creating a texture.
+pub enum TextureError {
+    /// The image file could not be opened or decoded.
+    Image { path: PathBuf, error: ImageError },
+    /// The pixel format has no OpenGL counterpart.
+    UnsupportedFormat(ColorType),
+    /// The texture is larger than `GL_MAX_TEXTURE_SIZE` in at least one dimension.
+    TooLarge { width: u32, height: u32, max: u32 },
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Image { path, error } => write!(f, "failed to load {}: {}", path.display(), error),
+            TextureError::UnsupportedFormat(color) => write!(f, "unsupported pixel format {:?}", color),
+            TextureError::TooLarge { width, height, max } => {
+                write!(f, "{}x{} image is larger than the maximum texture size of {}", width, height, max)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Error for TextureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TextureError::Image { error, .. } => Some(error),
+            _ => None,
         }
     }
 }
 
+/// Internal format of a texture and format of the pixels uploaded to it.
+struct PixelFormat {
+    internal: GLenum,
+    format: GLenum,
+    ty: GLenum,
+    /// Components sampled as red, green, blue and alpha, if not the default ones.
+    swizzle: Option<[GLenum; 4]>,
+}
+
+fn pixel_format(color: ColorType, srgb: bool) -> Option<PixelFormat> {
+    let gray = Some([gl::RED, gl::RED, gl::RED, gl::ONE]);
+    let gray_alpha = Some([gl::RED, gl::RED, gl::RED, gl::GREEN]);
+    let (internal, format, ty, swizzle) = match (color, srgb) {
+        (ColorType::L8, false) => (gl::R8, gl::RED, gl::UNSIGNED_BYTE, gray),
+        (ColorType::La8, false) => (gl::RG8, gl::RG, gl::UNSIGNED_BYTE, gray_alpha),
+        (ColorType::Rgb8, false) => (gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE, None),
+        (ColorType::Rgb8, true) => (gl::SRGB8, gl::RGB, gl::UNSIGNED_BYTE, None),
+        (ColorType::Rgba8, false) => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE, None),
+        (ColorType::Rgba8, true) => (gl::SRGB8_ALPHA8, gl::RGBA, gl::UNSIGNED_BYTE, None),
+        (ColorType::Bgr8, false) => (gl::RGB8, gl::BGR, gl::UNSIGNED_BYTE, None),
+        (ColorType::Bgr8, true) => (gl::SRGB8, gl::BGR, gl::UNSIGNED_BYTE, None),
+        (ColorType::Bgra8, false) => (gl::RGBA8, gl::BGRA, gl::UNSIGNED_BYTE, None),
+        (ColorType::Bgra8, true) => (gl::SRGB8_ALPHA8, gl::BGRA, gl::UNSIGNED_BYTE, None),
+        // 16-bit samples are in native byte order in memory, as OpenGL expects them
+        (ColorType::L16, false) => (gl::R16, gl::RED, gl::UNSIGNED_SHORT, gray),
+        (ColorType::La16, false) => (gl::RG16, gl::RG, gl::UNSIGNED_SHORT, gray_alpha),
+        (ColorType::Rgb16, false) => (gl::RGB16, gl::RGB, gl::UNSIGNED_SHORT, None),
+        (ColorType::Rgba16, false) => (gl::RGBA16, gl::RGBA, gl::UNSIGNED_SHORT, None),
+        _ => return None,
+    };
+    Some(PixelFormat { internal, format, ty, swizzle })
+}
+
 pub struct Texture2dDescriptor<'a> {
     pub unit: GLuint,
     pub img: &'a RgbaImage,
@@ -49,30 +369,50 @@ pub struct Texture2dDescriptor<'a> {
 }
 
 pub fn create_2d(desc: &Texture2dDescriptor) -> Texture2d {
+    let format = PixelFormat { internal: gl::RGBA8, format: gl::RGBA, ty: gl::UNSIGNED_BYTE, swizzle: None };
+    create(desc.unit, desc.params, desc.img.width(), desc.img.height(), &format, desc.img.as_ptr() as *const c_void)
+}
+
+/// Check that a texture of `width` by `height` fits in `GL_MAX_TEXTURE_SIZE`.
+fn check_size(width: u32, height: u32) -> Result<(), TextureError> {
+    let mut max = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max);
+    }
+    if width > max as u32 || height > max as u32 {
+        return Err(TextureError::TooLarge { width, height, max: max as u32 });
+    }
+    Ok(())
+}
+
+fn create(unit: GLuint, params: &Texture2dParams, width: u32, height: u32, format: &PixelFormat,
+          data: *const c_void) -> Texture2d {
     let mut id = 0;
     unsafe {
         gl::GenTextures(1, &mut id);
-        gl::ActiveTexture(desc.unit);
+        gl::ActiveTexture(unit);
         gl::BindTexture(gl::TEXTURE_2D, id);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, desc.params.s_mode);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, desc.params.t_mode);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, desc.params.min_filter);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, desc.params.mag_filter);
+        params.apply();
+        if let Some(swizzle) = format.swizzle {
+            let swizzle = swizzle.map(|c| c as GLint);
+            gl::TexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+        }
 
         gl::TexImage2D(
             gl::TEXTURE_2D,
             0,
-            gl::RGBA8 as GLint,
-            desc.img.width() as GLint,
-            desc.img.height() as GLint,
+            format.internal as GLint,
+            width as GLint,
+            height as GLint,
             0,
-            gl::RGBA,
-            gl::UNSIGNED_BYTE,
-            desc.img.as_ptr() as *const c_void);
+            format.format,
+            format.ty,
+            data);
+        // Without them the texture would be incomplete and sample black
+        if params.min_filter.uses_mipmaps() {
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+        }
     }
 
-    Texture2d {
-        id,
-        unit: desc.unit,
-    }
-}
\ No newline at end of file
+    Texture2d { id, unit }
+}
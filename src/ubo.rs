@@ -0,0 +1,251 @@
+use std::ffi::c_void;
+use std::marker::PhantomData;
+
+use gl::{self, types::*};
+use nalgebra::{Isometry3, Matrix2, Matrix3, Matrix4, Perspective3, Vector2, Vector3, Vector4};
+
+pub use learnopengl_rs_derive::Std140;
+
+/// A type that can be written to a uniform buffer with the std140 layout of uniform blocks.
+///
+/// Derive it with `#[derive(Std140)]` for structs whose fields all implement it. The Rust layout
+/// of the struct does not matter, fields are copied to their std140 offsets.
+pub trait Std140 {
+    /// Base alignment in bytes.
+    const ALIGNMENT: usize;
+    /// Size in bytes. The size of arrays and structures includes their trailing padding.
+    const SIZE: usize;
+
+    /// Write the std140 representation of the value to `buf`, which is `SIZE` bytes long.
+    fn write_std140(&self, buf: &mut [u8]);
+}
+
+/// Round `offset` up to a multiple of `alignment`.
+pub const fn align_to(offset: usize, alignment: usize) -> usize {
+    offset.div_ceil(alignment) * alignment
+}
+
+/// Scalars are stored as 4 bytes, booleans as `uint`s.
+macro_rules! impl_std140_scalar {
+    ($($ty:ty => $to_bits:expr),*) => {
+        $(
+            impl Std140 for $ty {
+                const ALIGNMENT: usize = 4;
+                const SIZE: usize = 4;
+
+                fn write_std140(&self, buf: &mut [u8]) {
+                    let to_bits: fn(&$ty) -> u32 = $to_bits;
+                    buf.copy_from_slice(&to_bits(self).to_ne_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_std140_scalar!(
+    f32 => |v| v.to_bits(),
+    i32 => |v| *v as u32,
+    u32 => |v| *v,
+    bool => |v| *v as u32
+);
+
+/// `vec2` are aligned on 8 bytes, `vec3` and `vec4` on 16 bytes.
+macro_rules! impl_std140_vector {
+    ($($vector:ident<$scalar:ty>, $n:expr, $alignment:expr);*) => {
+        $(
+            impl Std140 for $vector<$scalar> {
+                const ALIGNMENT: usize = $alignment;
+                const SIZE: usize = 4 * $n;
+
+                fn write_std140(&self, buf: &mut [u8]) {
+                    for (i, v) in self.iter().enumerate() {
+                        v.write_std140(&mut buf[4 * i..4 * i + 4]);
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_std140_vector!(
+    Vector2<f32>, 2, 8; Vector3<f32>, 3, 16; Vector4<f32>, 4, 16;
+    Vector2<i32>, 2, 8; Vector3<i32>, 3, 16; Vector4<i32>, 4, 16;
+    Vector2<u32>, 2, 8; Vector3<u32>, 3, 16; Vector4<u32>, 4, 16
+);
+
+/// Matrices are stored as arrays of column vectors, each aligned on 16 bytes.
+macro_rules! impl_std140_matrix {
+    ($($matrix:ident, $n:expr);*) => {
+        $(
+            impl Std140 for $matrix<f32> {
+                const ALIGNMENT: usize = 16;
+                const SIZE: usize = 16 * $n;
+
+                fn write_std140(&self, buf: &mut [u8]) {
+                    for (j, column) in self.column_iter().enumerate() {
+                        for (i, v) in column.iter().enumerate() {
+                            v.write_std140(&mut buf[16 * j + 4 * i..16 * j + 4 * i + 4]);
+                        }
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_std140_matrix!(Matrix2, 2; Matrix3, 3; Matrix4, 4);
+
+/// Stored as a `mat4`.
+impl Std140 for Isometry3<f32> {
+    const ALIGNMENT: usize = 16;
+    const SIZE: usize = 64;
+
+    fn write_std140(&self, buf: &mut [u8]) {
+        self.to_homogeneous().write_std140(buf);
+    }
+}
+
+/// Stored as a `mat4`.
+impl Std140 for Perspective3<f32> {
+    const ALIGNMENT: usize = 16;
+    const SIZE: usize = 64;
+
+    fn write_std140(&self, buf: &mut [u8]) {
+        self.as_matrix().write_std140(buf);
+    }
+}
+
+/// Array elements are aligned on 16 bytes at least.
+impl<T: Std140, const N: usize> Std140 for [T; N] {
+    const ALIGNMENT: usize = if T::ALIGNMENT > 16 { T::ALIGNMENT } else { 16 };
+    const SIZE: usize = align_to(T::SIZE, Self::ALIGNMENT) * N;
+
+    fn write_std140(&self, buf: &mut [u8]) {
+        let stride = align_to(T::SIZE, Self::ALIGNMENT);
+        for (i, v) in self.iter().enumerate() {
+            v.write_std140(&mut buf[i * stride..i * stride + T::SIZE]);
+        }
+    }
+}
+
+/// A uniform buffer object holding a value of type `T`, laid out with std140.
+///
+/// Bind it to a binding point with `bind` and point the uniform blocks of programs to the same
+/// binding point with `ShaderProgram::bind_uniform_block`, so that programs share its data.
+pub struct UniformBuffer<T: Std140> {
+    pub id: GLuint,
+    marker: PhantomData<T>,
+}
+
+impl<T: Std140> Default for UniformBuffer<T> {
+    fn default() -> Self {
+        Self { id: 0, marker: PhantomData }
+    }
+}
+
+impl<T: Std140> UniformBuffer<T> {
+    /// Create a buffer holding `value`.
+    pub fn new(value: &T) -> Self {
+        let buf = Self::bytes(value);
+        let mut id = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, id);
+            gl::BufferData(gl::UNIFORM_BUFFER, buf.len() as GLsizeiptr, buf.as_ptr() as *const c_void,
+                           gl::DYNAMIC_DRAW);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+        Self { id, marker: PhantomData }
+    }
+
+    fn bytes(value: &T) -> Vec<u8> {
+        let mut buf = vec![0; T::SIZE];
+        value.write_std140(&mut buf);
+        buf
+    }
+
+    /// Replace the content of the buffer with `value`.
+    pub fn update(&self, value: &T) {
+        let buf = Self::bytes(value);
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.id);
+            gl::BufferSubData(gl::UNIFORM_BUFFER, 0, buf.len() as GLsizeiptr, buf.as_ptr() as *const c_void);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+    }
+
+    /// Bind the buffer to the uniform buffer binding point `binding`.
+    ///
+    /// See [glBindBufferBase](https://docs.gl/gl3/glBindBufferBase) for details.
+    pub fn bind(&self, binding: GLuint) {
+        unsafe {
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, self.id);
+        }
+    }
+}
+
+impl<T: Std140> Drop for UniformBuffer<T> {
+    fn drop(&mut self) {
+        log::debug!("Dropping ubo {}", self.id);
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Std140)]
+    struct Light {
+        position: Vector3<f32>,
+        intensity: f32,
+        color: Vector3<f32>,
+    }
+
+    #[derive(Std140)]
+    struct Scene {
+        enabled: bool,
+        ambient: Vector2<f32>,
+        lights: [Light; 2],
+        weights: [f32; 3],
+        model: Matrix3<f32>,
+    }
+
+    #[test]
+    fn std140_layout() {
+        // vec3 + float share 16 bytes, the struct is padded to a multiple of 16
+        assert_eq!(Light::ALIGNMENT, 16);
+        assert_eq!(Light::SIZE, 32);
+        // bool at 0, vec2 at 8, lights at 16, weights at 80 with a 16 bytes stride, mat3 at 128
+        assert_eq!(Scene::SIZE, 176);
+
+        let scene = Scene {
+            enabled: true,
+            ambient: Vector2::new(0.5, 0.25),
+            lights: [
+                Light { position: Vector3::new(1.0, 2.0, 3.0), intensity: 4.0, color: Vector3::new(5.0, 6.0, 7.0) },
+                Light { position: Vector3::new(8.0, 9.0, 10.0), intensity: 11.0, color: Vector3::new(12.0, 13.0, 14.0) },
+            ],
+            weights: [0.1, 0.2, 0.3],
+            model: Matrix3::identity(),
+        };
+        let mut buf = vec![0; Scene::SIZE];
+        scene.write_std140(&mut buf);
+        let word = |offset: usize| u32::from_ne_bytes([buf[offset], buf[offset + 1], buf[offset + 2], buf[offset + 3]]);
+        let float = |offset: usize| f32::from_bits(word(offset));
+        assert_eq!(word(0), 1);
+        assert_eq!(float(8), 0.5);
+        assert_eq!(float(12), 0.25);
+        assert_eq!(float(16 + 12), 4.0);
+        assert_eq!(float(16 + 16), 5.0);
+        assert_eq!(float(48 + 12), 11.0);
+        assert_eq!(float(80), 0.1);
+        assert_eq!(float(96), 0.2);
+        assert_eq!(float(112), 0.3);
+        assert_eq!(float(128), 1.0);
+        assert_eq!(float(144 + 4), 1.0);
+        assert_eq!(float(160 + 8), 1.0);
+    }
+}
@@ -1,70 +1,222 @@
 use std::ffi::c_void;
+use std::mem::size_of;
 
 use gl::{self, types::*};
+use nalgebra::{Matrix2, Matrix3, Matrix4, Point2, Point3, Scalar, Vector2, Vector3, Vector4};
 
-struct VertexBufferObject {
-    id: GLuint,
+pub use learnopengl_rs_derive::Vertex;
+
+use crate::buffer::{Buffer, BufferUsage};
+use crate::context;
+
+pub mod packed;
+
+/// A vertex array along with its buffers, drawn with `draw` and its variants.
+///
+/// Vertex attributes can be interleaved in a single buffer, see `create`, or spread over several
+/// buffers with their own layouts, e.g. positions, normals and skinning data as most asset
+/// formats deliver them, see `attach_vertices`.
+pub struct VertexArrayObject {
+    pub id: GLuint,
+    /// Buffer the vertex attributes are read from, the first one if there are several.
+    pub vbo: Buffer,
+    /// Buffers of the other vertex attributes, in the order they were attached.
+    pub buffers: Vec<Buffer>,
+    /// Buffer of the indices, with id 0 for vertex arrays created without indices.
+    pub ebo: Buffer,
+    /// Buffer of the per-instance attributes, with id 0 until `attach_instances` is called.
+    pub instances: Buffer,
+    /// Primitive type to draw, `gl::TRIANGLES` by default.
+    pub mode: GLenum,
+    /// Whether the largest value of the index type restarts the primitive, e.g. to draw several
+    /// triangle strips at once. Only applies to indexed vertex arrays.
+    pub primitive_restart: bool,
+    /// Size of a vertex in the vertex buffer, in bytes.
+    vertex_size: usize,
+    /// Size of an instance in the instance buffer, in bytes.
+    instance_size: usize,
+    /// Locations of the per-instance attributes, to reset when the instance buffer is replaced.
+    instance_locations: Vec<GLuint>,
+    /// Type of the indices, 0 for vertex arrays created without indices.
+    index_type: GLenum,
 }
 
-impl Default for VertexBufferObject {
+impl Default for VertexArrayObject {
     fn default() -> Self {
-        Self { id: 0 }
+        Self {
+            id: 0,
+            vbo: Buffer::default(),
+            buffers: Vec::new(),
+            ebo: Buffer::default(),
+            instances: Buffer::default(),
+            mode: gl::TRIANGLES,
+            primitive_restart: false,
+            vertex_size: 0,
+            instance_size: 0,
+            instance_locations: Vec::new(),
+            index_type: 0,
+        }
     }
 }
 
-impl Drop for VertexBufferObject {
-    fn drop(&mut self) {
-        println!("Dropping vbo {}", self.id);
+impl VertexArrayObject {
+    /// Create a vertex array without buffers, to attach them with `attach_vertices`,
+    /// `attach_indices` and `attach_instances`.
+    ///
+    /// See [glGenVertexArrays](https://docs.gl/gl3/glGenVertexArrays) for details.
+    pub fn new() -> Self {
+        let mut vao = Self::default();
         unsafe {
-            gl::DeleteBuffers(1, &self.id);
+            gl::GenVertexArrays(1, &mut vao.id);
         }
+        vao
     }
-}
 
-struct ElementBufferObject {
-    id: GLuint,
-}
+    /// Add a buffer holding `data`, the attributes `attrib` are read from.
+    ///
+    /// The first buffer attached is `vbo`, which gives the number of vertices, the following ones
+    /// are pushed to `buffers` and are expected to hold as many vertices.
+    pub fn attach_vertices<T>(&mut self, data: &[T], attrib: &[VertexAttribPointer], usage: BufferUsage) {
+        let buffer = unsafe {
+            gl::BindVertexArray(self.id);
+            let buffer = Buffer::new(gl::ARRAY_BUFFER, data, usage);
+            for attr in attrib {
+                set_attrib_pointer(attr);
+            }
+            buffer
+        };
+        if self.vbo.id == 0 {
+            self.vbo = buffer;
+            self.vertex_size = size_of::<T>();
+        } else {
+            self.buffers.push(buffer);
+        }
+    }
 
-impl Default for ElementBufferObject {
-    fn default() -> Self {
-        Self { id: 0 }
+    /// Set the buffer of the indices, replacing the previous one.
+    pub fn attach_indices<I: Index>(&mut self, indices: &[I], usage: BufferUsage) {
+        unsafe {
+            // The bound vertex array records the element array buffer
+            gl::BindVertexArray(self.id);
+            self.ebo = Buffer::new(gl::ELEMENT_ARRAY_BUFFER, indices, usage);
+        }
+        self.index_type = I::TYPE;
     }
-}
 
-impl Drop for ElementBufferObject {
-    fn drop(&mut self) {
-        println!("Dropping ebo {}", self.id);
+    pub fn is_indexed(&self) -> bool {
+        self.index_type != 0
+    }
+
+    /// Number of vertices in the vertex buffer.
+    pub fn vertex_count(&self) -> usize {
+        self.vbo.size().checked_div(self.vertex_size).unwrap_or(0)
+    }
+
+    /// Number of indices in the index buffer, 0 if the vertex array is not indexed.
+    pub fn index_count(&self) -> usize {
+        if self.is_indexed() { self.ebo.size() / index_size(self.index_type) } else { 0 }
+    }
+
+    /// Number of instances in the instance buffer.
+    pub fn instance_count(&self) -> usize {
+        self.instances.size().checked_div(self.instance_size).unwrap_or(0)
+    }
+
+    /// Add a buffer of per-instance attributes holding `data`, replacing the previous one.
+    ///
+    /// Attributes without a divisor advance once per instance. The attributes of the previous
+    /// buffer are disabled. Update the buffer through `instances`, e.g. every frame, and draw the
+    /// instances with `draw_instanced`.
+    pub fn attach_instances<T>(&mut self, data: &[T], attrib: &[VertexAttribPointer], usage: BufferUsage) {
         unsafe {
-            gl::DeleteBuffers(1, &self.id);
+            gl::BindVertexArray(self.id);
+            for &index in &self.instance_locations {
+                gl::DisableVertexAttribArray(index);
+                gl::VertexAttribDivisor(index, 0);
+            }
+            self.instances = Buffer::new(gl::ARRAY_BUFFER, data, usage);
+            for attr in attrib {
+                set_attrib_pointer(&VertexAttribPointer { divisor: attr.divisor.max(1), ..*attr });
+            }
         }
+        self.instance_size = size_of::<T>();
+        self.instance_locations = attrib.iter().map(|attr| attr.index).collect();
     }
-}
 
-pub struct VertexArrayObject {
-    pub id: GLuint,
-    vbo: VertexBufferObject,
-    ebo: ElementBufferObject,
-}
+    /// Number of indices drawn by `draw`, or of vertices if the vertex array is not indexed.
+    /// It follows the size of the buffers when they are resized.
+    pub fn count(&self) -> usize {
+        if self.is_indexed() { self.index_count() } else { self.vertex_count() }
+    }
 
-impl Default for VertexArrayObject {
-    fn default() -> Self {
-        Self {
-            id: 0,
-            vbo: VertexBufferObject::default(),
-            ebo: ElementBufferObject::default(),
+    /// Draw all the primitives.
+    ///
+    /// See [glDrawArrays](https://docs.gl/gl3/glDrawArrays) and
+    /// [glDrawElements](https://docs.gl/gl3/glDrawElements) for details.
+    pub fn draw(&self) {
+        self.draw_range(0, self.count());
+    }
+
+    /// Draw `count` indices starting at index `first`, or `count` vertices starting at vertex
+    /// `first` if the vertex array is not indexed.
+    pub fn draw_range(&self, first: usize, count: usize) {
+        self.draw_instances(first, count, 1);
+    }
+
+    /// Draw all the primitives `instances` times, with `gl_InstanceID` going from 0 to
+    /// `instances - 1`.
+    ///
+    /// See [glDrawArraysInstanced](https://docs.gl/gl3/glDrawArraysInstanced) and
+    /// [glDrawElementsInstanced](https://docs.gl/gl3/glDrawElementsInstanced) for details.
+    pub fn draw_instanced(&self, instances: usize) {
+        self.draw_instances(0, self.count(), instances);
+    }
+
+    fn draw_instances(&self, first: usize, count: usize, instances: usize) {
+        debug_assert!(first + count <= self.count(),
+                      "drawing {} elements from {} out of vertex array {} of {} elements",
+                      count, first, self.id, self.count());
+        unsafe {
+            gl::BindVertexArray(self.id);
+            if !self.is_indexed() {
+                gl::DrawArraysInstanced(self.mode, first as GLint, count as GLsizei, instances as GLsizei);
+                return;
+            }
+            if self.primitive_restart {
+                gl::Enable(gl::PRIMITIVE_RESTART);
+                gl::PrimitiveRestartIndex(restart_index(self.index_type));
+            }
+            let offset = (first * index_size(self.index_type)) as *const c_void;
+            gl::DrawElementsInstanced(self.mode, count as GLsizei, self.index_type, offset, instances as GLsizei);
+            if self.primitive_restart {
+                gl::Disable(gl::PRIMITIVE_RESTART);
+            }
         }
     }
 }
 
 impl Drop for VertexArrayObject {
     fn drop(&mut self) {
-        println!("Dropping vao {}", self.id);
+        log::debug!("Dropping vao {}", self.id);
         unsafe {
             gl::DeleteVertexArrays(1, &self.id);
         }
     }
 }
 
+/// How the shader reads the components of an attribute.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttributeKind {
+    /// Converted to floats, e.g. `vec3` or a normalized `[u8; 4]` color, the default.
+    Float,
+    /// Kept as integers, for `int`, `uint`, `ivecN` and `uvecN` inputs such as bone indices.
+    Integer,
+    /// Kept as doubles, for `double` and `dvecN` inputs. Requires OpenGL 4.1 or
+    /// `GL_ARB_vertex_attrib_64bit`.
+    Double,
+}
+
+#[derive(Clone, Copy)]
 pub struct VertexAttribPointer {
     pub index: GLuint,
     pub size: GLint,
@@ -72,6 +224,11 @@ pub struct VertexAttribPointer {
     pub normalized: GLboolean,
     pub stride: GLsizei,
     pub pointer: *const c_void,
+    /// Number of instances drawn before the attribute advances, 0 for per-vertex attributes.
+    pub divisor: GLuint,
+    /// How the shader reads the values, which must match the base type of its input, e.g.
+    /// `AttributeKind::Integer` for an `ivec4`. See `ShaderProgram::check_vertex_attribs`.
+    pub kind: AttributeKind,
 }
 
 impl Default for VertexAttribPointer {
@@ -83,62 +240,303 @@ impl Default for VertexAttribPointer {
             normalized: gl::FALSE as GLboolean,
             stride: 0,
             pointer: std::ptr::null(),
+            divisor: 0,
+            kind: AttributeKind::Float,
         }
     }
 }
 
-pub fn create<T>(data: &[T], attrib: &[VertexAttribPointer]) -> VertexArrayObject {
-    unsafe {
-        let mut id: GLuint = 0;
+impl VertexAttribPointer {
+    /// The attribute at location `index`, read from a buffer holding only values of type `T`,
+    /// e.g. `VertexAttribPointer::of::<[f32; 3]>(1)` for a buffer of normals.
+    /// Matrices take several locations and are not supported, derive `Vertex` instead.
+    pub fn of<T: VertexAttribute>(index: GLuint) -> Self {
+        assert_eq!(T::LOCATIONS, 1, "matrix attributes take several locations");
+        Self { index, size: T::SIZE, ty: T::TYPE, ..Self::default() }
+    }
 
-        gl::GenVertexArrays(1, &mut id);
-        gl::BindVertexArray(id);
+    /// Check that `ty` can be read as `kind`: integer attributes need an integer type and are
+    /// not normalized, double attributes need `gl::DOUBLE`.
+    pub fn validate(&self) -> Result<(), String> {
+        let integer = matches!(self.ty, gl::BYTE | gl::UNSIGNED_BYTE | gl::SHORT | gl::UNSIGNED_SHORT
+                                        | gl::INT | gl::UNSIGNED_INT);
+        match self.kind {
+            AttributeKind::Float => Ok(()),
+            AttributeKind::Integer if !integer => {
+                Err(format!("integer attribute {} has the non-integer type 0x{:x}", self.index, self.ty))
+            }
+            AttributeKind::Double if self.ty != gl::DOUBLE => {
+                Err(format!("double attribute {} has the type 0x{:x} instead of GL_DOUBLE", self.index, self.ty))
+            }
+            _ if self.normalized != gl::FALSE => {
+                Err(format!("{:?} attribute {} cannot be normalized", self.kind, self.index))
+            }
+            _ => Ok(()),
+        }
+    }
+}
 
-        // Create a Vertex Buffer Object and copy the vertex data to it
-        let vbo = create_vbo(data);
+/// A vertex type, whose attributes can be read from a buffer of vertices.
+///
+/// Derive it with `#[derive(Vertex)]` rather than writing the attributes by hand, so that their
+/// types, offsets and stride follow the definition of the struct. Fields whose locations
+/// overlap are compile errors:
+///
+/// ```compile_fail
+/// # use learnopengl_rs::vao::Vertex;
+/// # use nalgebra::Matrix4;
+/// #[derive(Vertex)]
+/// #[repr(C)]
+/// struct Instance {
+///     model: Matrix4<f32>,
+///     // The matrix takes the locations 0 to 3
+///     #[location = 2]
+///     color: [f32; 3],
+/// }
+/// ```
+pub trait Vertex {
+    /// The attributes of the vertex, to pass to `create`.
+    fn attributes() -> Vec<VertexAttribPointer>;
+}
 
-        for attr in attrib {
-            gl::EnableVertexAttribArray(attr.index);
-            gl::VertexAttribPointer(attr.index, attr.size, attr.ty, attr.normalized, attr.stride, attr.pointer);
+/// A component type of vertex attributes.
+pub trait VertexComponent {
+    /// OpenGL type, e.g. `gl::FLOAT`.
+    const TYPE: GLenum;
+}
+
+/// A type that can be read as a vertex attribute, made of 1 to 4 components, or as several
+/// consecutive attributes of the same size for matrices.
+pub trait VertexAttribute {
+    /// Number of components, per column for matrices.
+    const SIZE: GLint;
+    /// OpenGL type of the components.
+    const TYPE: GLenum;
+    /// Number of locations taken, e.g. the number of columns of a matrix.
+    const LOCATIONS: GLuint = 1;
+}
+
+macro_rules! impl_vertex_component {
+    ($($ty:ty => $gl_type:expr),*) => {
+        $(
+            impl VertexComponent for $ty {
+                const TYPE: GLenum = $gl_type;
+            }
+
+            impl VertexAttribute for $ty {
+                const SIZE: GLint = 1;
+                const TYPE: GLenum = $gl_type;
+            }
+        )*
+    };
+}
+
+impl_vertex_component!(
+    f32 => gl::FLOAT, f64 => gl::DOUBLE,
+    i8 => gl::BYTE, u8 => gl::UNSIGNED_BYTE,
+    i16 => gl::SHORT, u16 => gl::UNSIGNED_SHORT,
+    i32 => gl::INT, u32 => gl::UNSIGNED_INT
+);
+
+impl<T: VertexComponent, const N: usize> VertexAttribute for [T; N] {
+    const SIZE: GLint = {
+        assert!(N >= 1 && N <= 4, "vertex attributes have 1 to 4 components");
+        N as GLint
+    };
+    const TYPE: GLenum = T::TYPE;
+}
+
+macro_rules! impl_vertex_attribute_vector {
+    ($($vector:ident, $n:expr);*) => {
+        $(
+            impl<T: VertexComponent + Scalar> VertexAttribute for $vector<T> {
+                const SIZE: GLint = $n;
+                const TYPE: GLenum = T::TYPE;
+            }
+        )*
+    };
+}
+
+impl_vertex_attribute_vector!(Vector2, 2; Vector3, 3; Vector4, 4; Point2, 2; Point3, 3);
+
+/// Matrices take a location per column, e.g. `layout (location = 2) in mat4 model` takes the
+/// locations 2 to 5.
+macro_rules! impl_vertex_attribute_matrix {
+    ($($matrix:ident, $n:expr);*) => {
+        $(
+            impl<T: VertexComponent + Scalar> VertexAttribute for $matrix<T> {
+                const SIZE: GLint = $n;
+                const TYPE: GLenum = T::TYPE;
+                const LOCATIONS: GLuint = $n;
+            }
+        )*
+    };
+}
+
+impl_vertex_attribute_matrix!(Matrix2, 2; Matrix3, 3; Matrix4, 4);
+
+mod sealed {
+    /// Keeps `Index` from being implemented outside of this module.
+    pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+}
+
+/// A type of vertex indices: `u8`, `u16` or `u32`. It cannot be implemented for other types.
+pub trait Index: VertexComponent + sealed::Sealed {}
+
+impl Index for u8 {}
+impl Index for u16 {}
+impl Index for u32 {}
+
+/// Set the pointer of an attribute of the bound vertex array to the buffer bound to
+/// `GL_ARRAY_BUFFER`.
+///
+/// Panics if the attribute is invalid, see `VertexAttribPointer::validate`, or if it is a
+/// double attribute and the context does not support them.
+unsafe fn set_attrib_pointer(attr: &VertexAttribPointer) {
+    if let Err(e) = attr.validate() {
+        panic!("{}", e);
+    }
+    gl::EnableVertexAttribArray(attr.index);
+    match attr.kind {
+        AttributeKind::Float => {
+            gl::VertexAttribPointer(attr.index, attr.size, attr.ty, attr.normalized, attr.stride, attr.pointer)
         }
+        AttributeKind::Integer => gl::VertexAttribIPointer(attr.index, attr.size, attr.ty, attr.stride, attr.pointer),
+        AttributeKind::Double => {
+            assert!(gl::VertexAttribLPointer::is_loaded()
+                        && context::has_feature((4, 1), "GL_ARB_vertex_attrib_64bit"),
+                    "double attribute {} needs OpenGL 4.1 or GL_ARB_vertex_attrib_64bit", attr.index);
+            gl::VertexAttribLPointer(attr.index, attr.size, attr.ty, attr.stride, attr.pointer)
+        }
+    }
+    if attr.divisor != 0 {
+        gl::VertexAttribDivisor(attr.index, attr.divisor);
+    }
+}
+
+fn index_size(index_type: GLenum) -> usize {
+    match index_type {
+        gl::UNSIGNED_BYTE => 1,
+        gl::UNSIGNED_SHORT => 2,
+        _ => 4,
+    }
+}
 
-        VertexArrayObject { id, vbo, ebo: ElementBufferObject::default() }
+/// The largest value of the index type, which restarts the primitive when enabled.
+fn restart_index(index_type: GLenum) -> GLuint {
+    match index_type {
+        gl::UNSIGNED_BYTE => u8::MAX as GLuint,
+        gl::UNSIGNED_SHORT => u16::MAX as GLuint,
+        _ => u32::MAX,
     }
 }
 
-pub fn create_indexed<T>(data: &[T], attrib: &[VertexAttribPointer], indices: &[GLuint]) -> VertexArrayObject {
-    let mut vao = create(data, attrib);
-    let ebo = create_ebo(indices);
-    vao.ebo = ebo;
+pub fn create<T>(data: &[T], attrib: &[VertexAttribPointer]) -> VertexArrayObject {
+    create_with_usage(data, attrib, BufferUsage::Static)
+}
+
+/// Create a vertex array whose vertex buffer holds `data` and is expected to be updated as
+/// `usage` tells, through `VertexArrayObject::vbo`.
+pub fn create_with_usage<T>(data: &[T], attrib: &[VertexAttribPointer], usage: BufferUsage) -> VertexArrayObject {
+    let mut vao = VertexArrayObject::new();
+    vao.attach_vertices(data, attrib, usage);
     vao
 }
 
-fn create_vbo<T>(data: &[T]) -> VertexBufferObject {
-    let mut vbo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER,
-            (data.len() * std::mem::size_of::<T>()) as GLsizeiptr,
-            std::mem::transmute(&data[0]),
-            gl::STATIC_DRAW,
-        );
-    }
-    VertexBufferObject { id: vbo }
-}
-
-fn create_ebo(indices: &[GLuint]) -> ElementBufferObject {
-    let mut ebo = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut ebo);
-        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
-        gl::BufferData(
-            gl::ELEMENT_ARRAY_BUFFER,
-            (indices.len() * std::mem::size_of::<GLuint>()) as GLsizeiptr,
-            std::mem::transmute(&indices[0]),
-            gl::STATIC_DRAW,
-        );
-    }
-    ElementBufferObject { id: ebo }
-}
\ No newline at end of file
+pub fn create_indexed<T, I: Index>(data: &[T], attrib: &[VertexAttribPointer], indices: &[I]) -> VertexArrayObject {
+    create_indexed_with_usage(data, attrib, indices, BufferUsage::Static)
+}
+
+/// Create an indexed vertex array whose vertex and index buffers are expected to be updated as
+/// `usage` tells.
+pub fn create_indexed_with_usage<T, I: Index>(
+    data: &[T],
+    attrib: &[VertexAttribPointer],
+    indices: &[I],
+    usage: BufferUsage,
+) -> VertexArrayObject {
+    let mut vao = create_with_usage(data, attrib, usage);
+    vao.attach_indices(indices, usage);
+    vao
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Vertex)]
+    #[repr(C)]
+    struct Textured {
+        position: Vector3<f32>,
+        #[location = 3]
+        tex: [f32; 2],
+        #[normalized]
+        color: [u8; 4],
+    }
+
+    #[derive(Vertex)]
+    #[divisor = 1]
+    #[repr(C)]
+    struct Instance {
+        #[location = 2]
+        model: Matrix4<f32>,
+        tint: Vector3<f32>,
+    }
+
+    #[derive(Vertex)]
+    #[repr(C)]
+    struct Skinned {
+        #[integer]
+        bones: [u8; 4],
+        #[double]
+        weight: f64,
+    }
+
+    #[test]
+    fn vertex_attributes() {
+        let attributes = Textured::attributes();
+        let layout: Vec<_> = attributes
+            .iter()
+            .map(|a| (a.index, a.size, a.ty, a.normalized, a.stride, a.pointer as usize))
+            .collect();
+        assert_eq!(layout, [
+            (0, 3, gl::FLOAT, gl::FALSE, 24, 0),
+            (3, 2, gl::FLOAT, gl::FALSE, 24, 12),
+            (4, 4, gl::UNSIGNED_BYTE, gl::TRUE, 24, 20),
+        ]);
+        assert!(attributes.iter().all(|a| a.divisor == 0));
+    }
+
+    #[test]
+    fn instance_attributes() {
+        let attributes = Instance::attributes();
+        let layout: Vec<_> = attributes
+            .iter()
+            .map(|a| (a.index, a.size, a.stride, a.pointer as usize, a.divisor))
+            .collect();
+        // A location per column of the matrix
+        assert_eq!(layout, [
+            (2, 4, 76, 0, 1),
+            (3, 4, 76, 16, 1),
+            (4, 4, 76, 32, 1),
+            (5, 4, 76, 48, 1),
+            (6, 3, 76, 64, 1),
+        ]);
+    }
+
+    #[test]
+    fn attribute_kinds() {
+        let kinds: Vec<_> = Skinned::attributes().iter().map(|a| (a.kind, a.validate())).collect();
+        assert_eq!(kinds, [(AttributeKind::Integer, Ok(())), (AttributeKind::Double, Ok(()))]);
+        let integer = VertexAttribPointer { kind: AttributeKind::Integer, ..VertexAttribPointer::of::<[u8; 4]>(0) };
+        assert!(VertexAttribPointer { ty: gl::FLOAT, ..integer }.validate().is_err());
+        assert!(VertexAttribPointer { normalized: gl::TRUE, ..integer }.validate().is_err());
+        let double = VertexAttribPointer { kind: AttributeKind::Double, ..VertexAttribPointer::of::<f64>(1) };
+        assert!(double.validate().is_ok());
+        assert!(VertexAttribPointer { ty: gl::FLOAT, ..double }.validate().is_err());
+    }
+}
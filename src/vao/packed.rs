@@ -0,0 +1,111 @@
+use gl::{self, types::*};
+pub use half::f16;
+
+use super::{VertexAttribute, VertexComponent};
+
+impl VertexComponent for f16 {
+    const TYPE: GLenum = gl::HALF_FLOAT;
+}
+
+impl VertexAttribute for f16 {
+    const SIZE: GLint = 1;
+    const TYPE: GLenum = gl::HALF_FLOAT;
+}
+
+/// Four signed components packed in 32 bits, 10 bits for x, y and z and 2 bits for w, read by
+/// `GL_INT_2_10_10_10_REV` attributes.
+///
+/// Normals and tangents fit in it with about 3 decimal digits of precision, a third of the size
+/// of `[f32; 3]`. Mark the attribute `#[normalized]` to read the components in [-1, 1].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Packed1010102(pub u32);
+
+impl Packed1010102 {
+    /// Pack components in [-1, 1], w being rounded to -1, 0 or 1, e.g. the handedness of a
+    /// tangent frame.
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        let field = |value: f32, bits: u32| snorm(value, bits) as u32 & ((1 << bits) - 1);
+        Self(field(x, 10) | field(y, 10) << 10 | field(z, 10) << 20 | field(w, 2) << 30)
+    }
+
+    /// The components in [-1, 1], as a `#[normalized]` attribute reads them.
+    pub fn unpack(self) -> [f32; 4] {
+        // Shift each field to the top of an i32 and back to sign extend it
+        let field = |shift: u32, bits: u32| {
+            let value = (self.0 << (32 - shift - bits)) as i32 >> (32 - bits);
+            (value as f32 / ((1 << (bits - 1)) - 1) as f32).max(-1.0)
+        };
+        [field(0, 10), field(10, 10), field(20, 10), field(30, 2)]
+    }
+}
+
+impl VertexAttribute for Packed1010102 {
+    const SIZE: GLint = 4;
+    const TYPE: GLenum = gl::INT_2_10_10_10_REV;
+}
+
+/// `value` in [-1, 1] as a signed normalized integer of `bits` bits.
+///
+/// Uses the conversion of OpenGL 4.2 and later, where -1, 0 and 1 are represented exactly;
+/// earlier versions read the values slightly off.
+fn snorm(value: f32, bits: u32) -> i32 {
+    let max = ((1 << (bits - 1)) - 1) as f32;
+    (value.clamp(-1.0, 1.0) * max).round() as i32
+}
+
+/// `value` in [-1, 1] as an `i8`, read back by a `#[normalized]` attribute.
+pub fn snorm8(value: f32) -> i8 {
+    snorm(value, 8) as i8
+}
+
+/// `value` in [-1, 1] as an `i16`, read back by a `#[normalized]` attribute.
+pub fn snorm16(value: f32) -> i16 {
+    snorm(value, 16) as i16
+}
+
+/// Convert positions or texture coordinates to half floats, with about 3 decimal digits of
+/// precision and a range of ±65504.
+pub fn to_f16<const N: usize>(data: &[[f32; N]]) -> Vec<[f16; N]> {
+    data.iter().map(|v| v.map(f16::from_f32)).collect()
+}
+
+/// Convert normals or other components in [-1, 1] to `i8`, see `snorm8`.
+pub fn to_snorm8<const N: usize>(data: &[[f32; N]]) -> Vec<[i8; N]> {
+    data.iter().map(|v| v.map(snorm8)).collect()
+}
+
+/// Convert normals or other components in [-1, 1] to `i16`, see `snorm16`.
+pub fn to_snorm16<const N: usize>(data: &[[f32; N]]) -> Vec<[i16; N]> {
+    data.iter().map(|v| v.map(snorm16)).collect()
+}
+
+/// Pack normals, with w set to 0.
+pub fn to_packed_normals(data: &[[f32; 3]]) -> Vec<Packed1010102> {
+    data.iter().map(|[x, y, z]| Packed1010102::new(*x, *y, *z, 0.0)).collect()
+}
+
+/// Pack tangents whose w is the handedness of the tangent frame, -1 or 1.
+pub fn to_packed_tangents(data: &[[f32; 4]]) -> Vec<Packed1010102> {
+    data.iter().map(|[x, y, z, w]| Packed1010102::new(*x, *y, *z, *w)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snorm_conversions() {
+        assert_eq!(to_snorm8(&[[-1.0, 0.0, 1.0, 2.0]]), [[-127, 0, 127, 127]]);
+        assert_eq!(to_snorm16(&[[-1.0, 0.5]]), [[-32767, 16384]]);
+        assert_eq!(to_f16(&[[0.5f32, -2.0]]), [[f16::from_f32(0.5), f16::from_f32(-2.0)]]);
+    }
+
+    #[test]
+    fn packed_1010102() {
+        assert_eq!(Packed1010102::new(1.0, 0.0, 0.0, 0.0).0, 0x1ff);
+        assert_eq!(Packed1010102::new(0.0, -1.0, 0.0, 1.0).0, 0x201 << 10 | 1 << 30);
+        assert_eq!(Packed1010102::new(0.25, -0.5, 1.0, -1.0).unpack(), [128.0 / 511.0, -256.0 / 511.0, 1.0, -1.0]);
+        assert_eq!(to_packed_normals(&[[0.0, 0.0, 1.0]])[0].unpack(), [0.0, 0.0, 1.0, 0.0]);
+    }
+}
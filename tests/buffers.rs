@@ -0,0 +1,56 @@
+//! Buffer tests, run in an offscreen context.
+
+use std::ffi::c_void;
+use std::mem::size_of;
+
+use gl::types::*;
+
+use learnopengl_rs::buffer::{Buffer, BufferUsage};
+use learnopengl_rs::headless::with_gl;
+
+/// Read the whole content of `buffer` back.
+fn content(buffer: &Buffer) -> Vec<u32> {
+    let mut data = vec![0u32; buffer.len::<u32>()];
+    unsafe {
+        gl::BindBuffer(gl::COPY_READ_BUFFER, buffer.id);
+        gl::GetBufferSubData(gl::COPY_READ_BUFFER, 0, buffer.size() as GLsizeiptr, data.as_mut_ptr() as *mut c_void);
+        gl::BindBuffer(gl::COPY_READ_BUFFER, 0);
+    }
+    data
+}
+
+#[test]
+fn buffers_are_updated() {
+    with_gl(|| {
+        let mut buffer = Buffer::new(gl::ARRAY_BUFFER, &[1u32, 2, 3, 4], BufferUsage::Dynamic);
+        assert_eq!(buffer.size(), 4 * size_of::<u32>());
+
+        buffer.update(1, &[20u32, 30]).unwrap();
+        assert_eq!(content(&buffer), [1, 20, 30, 4]);
+        assert!(buffer.update(3, &[40u32, 50]).is_err());
+
+        buffer.resize(&[5u32, 6, 7, 8, 9, 10]);
+        assert_eq!(content(&buffer), [5, 6, 7, 8, 9, 10]);
+
+        {
+            let mut range = buffer.map_range::<u32>(2, 3).unwrap();
+            range.write(0, 70);
+            range.write_slice(1, &[80, 90]);
+        }
+        assert_eq!(content(&buffer), [5, 6, 70, 80, 90, 10]);
+        assert!(buffer.map_range::<u32>(4, 3).is_err());
+
+        buffer.orphan();
+        assert_eq!(buffer.size(), 6 * size_of::<u32>());
+
+        let stream = Buffer::with_capacity::<u32>(gl::ARRAY_BUFFER, 8, BufferUsage::Stream);
+        assert_eq!(stream.len::<u32>(), 8);
+        stream.update(6, &[1u32, 2]).unwrap();
+        assert_eq!(content(&stream)[6..], [1, 2]);
+
+        unsafe {
+            assert_eq!(gl::GetError(), gl::NO_ERROR);
+        }
+    })
+    .unwrap();
+}
@@ -0,0 +1,60 @@
+//! Debug output tests, run in an offscreen context.
+
+use std::sync::Mutex;
+
+use gl::types::*;
+use log::{Level, Log, Metadata, Record};
+
+use learnopengl_rs::debug::{self, DebugConfig, Severity};
+use learnopengl_rs::headless::with_gl;
+
+/// Records the messages logged under the `gl` target.
+struct Recorder;
+
+static RECORDS: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+
+impl Log for Recorder {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target() == "gl"
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            RECORDS.lock().unwrap().push((record.level(), record.args().to_string()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Insert an application message of the given severity.
+fn insert_message(severity: GLenum, message: &str) {
+    unsafe {
+        gl::DebugMessageInsert(gl::DEBUG_SOURCE_APPLICATION, gl::DEBUG_TYPE_OTHER, 1, severity,
+                               message.len() as GLsizei, message.as_ptr() as *const GLchar);
+    }
+}
+
+#[test]
+fn messages_are_filtered_and_logged() {
+    log::set_logger(&Recorder).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+    with_gl(|| {
+        assert!(debug::enable(&DebugConfig { min_severity: Severity::Medium, ..DebugConfig::default() }));
+        insert_message(gl::DEBUG_SEVERITY_LOW, "filtered out");
+        insert_message(gl::DEBUG_SEVERITY_HIGH, "kept");
+        unsafe {
+            gl::BindBuffer(0xdead, 0);
+            while gl::GetError() != gl::NO_ERROR {}
+        }
+        debug::disable();
+    })
+    .unwrap();
+
+    let records = RECORDS.lock().unwrap();
+    assert!(records.iter().all(|(_, message)| !message.contains("filtered out")), "{:?}", records);
+    assert!(records.contains(&(Level::Error, "[application other] High #1: kept".to_owned())), "{:?}", records);
+    // Errors are reported whatever their severity
+    assert!(records.iter().any(|(level, message)| *level == Level::Error && message.starts_with("[api error]")),
+            "{:?}", records);
+}
@@ -0,0 +1,270 @@
+//! Golden image regression tests.
+//!
+//! Every example is rendered offscreen at a fixed simulated time and compared against its
+//! reference image in `res/golden`. A pixel fails when one of its channels differs from the
+//! reference by more than `Tolerance::max_channel_diff`, and a test fails when the ratio of
+//! failing pixels exceeds `Tolerance::max_failing_ratio`. Both can be overridden with the
+//! `GOLDEN_TOLERANCE` and `GOLDEN_MAX_FAILING_RATIO` environment variables. On failure the
+//! rendered frame and a diff image highlighting the failing pixels in red are written to
+//! `target/golden`.
+//!
+//! Set `GOLDEN_UPDATE=1` to (re)generate the reference images instead of comparing.
+
+use std::path::{Path, PathBuf};
+
+use image::{Rgba, RgbaImage};
+
+use learnopengl_rs::clock::ManualClock;
+use learnopengl_rs::headless::run_headless_with_clock;
+use learnopengl_rs::OpenGLApp;
+
+#[allow(dead_code)]
+#[path = "../examples/coordinate_systems.rs"]
+mod coordinate_systems;
+
+#[allow(dead_code)]
+#[path = "../examples/hello_triangle.rs"]
+mod hello_triangle;
+
+#[allow(dead_code)]
+#[path = "../examples/hello_triangle_ex1.rs"]
+mod hello_triangle_ex1;
+
+#[allow(dead_code)]
+#[path = "../examples/hello_triangle_ex2.rs"]
+mod hello_triangle_ex2;
+
+#[allow(dead_code)]
+#[path = "../examples/hello_triangle_ex3.rs"]
+mod hello_triangle_ex3;
+
+#[allow(dead_code)]
+#[path = "../examples/hello_triangle_indexed.rs"]
+mod hello_triangle_indexed;
+
+#[allow(dead_code)]
+#[path = "../examples/shaders.rs"]
+mod shaders;
+
+#[allow(dead_code)]
+#[path = "../examples/shaders_ex1.rs"]
+mod shaders_ex1;
+
+#[allow(dead_code)]
+#[path = "../examples/shaders_ex2.rs"]
+mod shaders_ex2;
+
+#[allow(dead_code)]
+#[path = "../examples/shaders_ex3.rs"]
+mod shaders_ex3;
+
+#[allow(dead_code)]
+#[path = "../examples/textures.rs"]
+mod textures;
+
+#[allow(dead_code)]
+#[path = "../examples/textures_ex1.rs"]
+mod textures_ex1;
+
+#[allow(dead_code)]
+#[path = "../examples/textures_ex2.rs"]
+mod textures_ex2;
+
+#[allow(dead_code)]
+#[path = "../examples/textures_ex4.rs"]
+mod textures_ex4;
+
+#[allow(dead_code)]
+#[path = "../examples/textures_multi.rs"]
+mod textures_multi;
+
+#[allow(dead_code)]
+#[path = "../examples/transformations.rs"]
+mod transformations;
+
+#[allow(dead_code)]
+#[path = "../examples/transformations_ex2.rs"]
+mod transformations_ex2;
+
+/// How far a rendered frame may drift from its reference.
+#[derive(Clone, Copy)]
+struct Tolerance {
+    /// Max per channel difference allowed between a rendered pixel and its reference.
+    max_channel_diff: u8,
+    /// Max ratio of pixels allowed to exceed `max_channel_diff`.
+    max_failing_ratio: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            max_channel_diff: 2,
+            max_failing_ratio: 0.001,
+        }
+    }
+}
+
+impl Tolerance {
+    fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            max_channel_diff: std::env::var("GOLDEN_TOLERANCE")
+                .ok()
+                .map(|t| t.parse().expect("GOLDEN_TOLERANCE must be an integer in 0..=255"))
+                .unwrap_or(default.max_channel_diff),
+            max_failing_ratio: std::env::var("GOLDEN_MAX_FAILING_RATIO")
+                .ok()
+                .map(|r| r.parse().expect("GOLDEN_MAX_FAILING_RATIO must be a number in 0..=1"))
+                .unwrap_or(default.max_failing_ratio),
+        }
+    }
+}
+
+/// Simulated time, in seconds, at which examples are rendered.
+///
+/// Animated examples are rendered at exactly this time, however long their setup takes, so
+/// their references only need to be regenerated when their animation changes.
+const SIMULATED_TIME: f32 = 2.5;
+
+fn output_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("target/golden")
+}
+
+/// Compare `actual` against `expected` and return the number of failing pixels
+/// along with a diff image: failing pixels in red over a dimmed copy of the reference.
+fn diff(expected: &RgbaImage, actual: &RgbaImage, tolerance: u8) -> (usize, RgbaImage) {
+    let mut failures = 0;
+    let mut diff = RgbaImage::new(expected.width(), expected.height());
+    for ((e, a), d) in expected.pixels().zip(actual.pixels()).zip(diff.pixels_mut()) {
+        let error = e.0.iter().zip(a.0.iter()).map(|(e, a)| (*e as i16 - *a as i16).abs()).max().unwrap();
+        *d = if error > tolerance as i16 {
+            failures += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let luma = (e[0] as u32 + e[1] as u32 + e[2] as u32) / 12;
+            Rgba([luma as u8, luma as u8, luma as u8, 255])
+        };
+    }
+    (failures, diff)
+}
+
+fn check<T: OpenGLApp>(name: &str, app: T) {
+    let frame = run_headless_with_clock(app, ManualClock::at(SIMULATED_TIME), 1).unwrap().pop().unwrap();
+
+    let reference = Path::new(env!("CARGO_MANIFEST_DIR")).join("res/golden").join(format!("{}.png", name));
+    if std::env::var_os("GOLDEN_UPDATE").is_some() {
+        frame.save(&reference).unwrap();
+        return;
+    }
+
+    let expected = image::open(&reference)
+        .unwrap_or_else(|e| panic!("Missing reference image {}: {} (run with GOLDEN_UPDATE=1)", reference.display(), e))
+        .into_rgba8();
+    assert_eq!(expected.dimensions(), frame.dimensions(), "{}: frame size differs from the reference", name);
+
+    let tolerance = Tolerance::from_env();
+    let (failures, diff) = diff(&expected, &frame, tolerance.max_channel_diff);
+    let failing_ratio = failures as f64 / (frame.width() * frame.height()) as f64;
+    if failing_ratio > tolerance.max_failing_ratio {
+        let dir = output_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let actual_path = dir.join(format!("{}.actual.png", name));
+        let diff_path = dir.join(format!("{}.diff.png", name));
+        frame.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{}: {} pixels differ from {} by more than {} (actual: {}, diff: {})",
+            name,
+            failures,
+            reference.display(),
+            tolerance.max_channel_diff,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+fn coordinate_systems() {
+    check("coordinate_systems", coordinate_systems::CoordinateSystems::new());
+}
+
+#[test]
+fn hello_triangle() {
+    check("hello_triangle", hello_triangle::HelloTriangle::new());
+}
+
+#[test]
+fn hello_triangle_ex1() {
+    check("hello_triangle_ex1", hello_triangle_ex1::HelloTriangleEx1::new());
+}
+
+#[test]
+fn hello_triangle_ex2() {
+    check("hello_triangle_ex2", hello_triangle_ex2::HelloTriangleEx2::new());
+}
+
+#[test]
+fn hello_triangle_ex3() {
+    check("hello_triangle_ex3", hello_triangle_ex3::HelloTriangleEx3::new());
+}
+
+#[test]
+fn hello_triangle_indexed() {
+    check("hello_triangle_indexed", hello_triangle_indexed::HelloTriangleIndexed::new());
+}
+
+#[test]
+fn shaders() {
+    check("shaders", shaders::Shaders::new());
+}
+
+#[test]
+fn shaders_ex1() {
+    check("shaders_ex1", shaders_ex1::ShadersEx1::new());
+}
+
+#[test]
+fn shaders_ex2() {
+    check("shaders_ex2", shaders_ex2::Shaders::new());
+}
+
+#[test]
+fn shaders_ex3() {
+    check("shaders_ex3", shaders_ex3::Shaders::new());
+}
+
+#[test]
+fn textures() {
+    check("textures", textures::Textures::new());
+}
+
+#[test]
+fn textures_ex1() {
+    check("textures_ex1", textures_ex1::MultiTextures::new());
+}
+
+#[test]
+fn textures_ex2() {
+    check("textures_ex2", textures_ex2::Textures::new());
+}
+
+#[test]
+fn textures_ex4() {
+    check("textures_ex4", textures_ex4::MultiTextures::new());
+}
+
+#[test]
+fn textures_multi() {
+    check("textures_multi", textures_multi::MultiTextures::new());
+}
+
+#[test]
+fn transformations() {
+    check("transformations", transformations::Transformations::new());
+}
+
+#[test]
+fn transformations_ex2() {
+    check("transformations_ex2", transformations_ex2::Transformations::new());
+}
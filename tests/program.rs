@@ -0,0 +1,112 @@
+//! Program linking tests, run in an offscreen context.
+
+use std::ffi::c_void;
+
+use gl::types::*;
+
+use learnopengl_rs::buffer::{Buffer, BufferUsage};
+use learnopengl_rs::headless::with_gl;
+use learnopengl_rs::shaders::{self, FeedbackMode, ProgramBuilder, ShaderProgram};
+use learnopengl_rs::vao::{VertexArrayObject, VertexAttribPointer};
+
+// No layout qualifiers, the locations come from the builder
+const VS: &str = "#version 330 core
+in vec2 aPos;
+in float aSize;
+out float vSize;
+void main() {
+    vSize = aSize;
+    gl_Position = vec4(aPos, 0.0, 1.0);
+}";
+
+const GS: &str = "#version 330 core
+layout (points) in;
+layout (points, max_vertices = 1) out;
+in float vSize[];
+out float gSize;
+out vec2 gPos;
+void main() {
+    gSize = 2.0 * vSize[0];
+    gPos = gl_in[0].gl_Position.xy;
+    gl_Position = gl_in[0].gl_Position;
+    EmitVertex();
+    EndPrimitive();
+}";
+
+const FS: &str = "#version 330 core
+in float gSize;
+out vec4 color;
+void main() {
+    color = vec4(gSize);
+}";
+
+/// Names of the transform feedback varyings of `program`.
+fn feedback_varyings(program: &ShaderProgram) -> Vec<String> {
+    let mut count = 0;
+    unsafe {
+        gl::GetProgramiv(program.id, gl::TRANSFORM_FEEDBACK_VARYINGS, &mut count);
+    }
+    (0..count as GLuint)
+        .map(|i| {
+            let mut name = [0u8; 64];
+            let (mut len, mut size, mut ty) = (0, 0, 0);
+            unsafe {
+                gl::GetTransformFeedbackVarying(program.id, i, name.len() as GLsizei, &mut len, &mut size, &mut ty,
+                                                name.as_mut_ptr() as *mut GLchar);
+            }
+            String::from_utf8_lossy(&name[..len as usize]).into_owned()
+        })
+        .collect()
+}
+
+#[test]
+fn geometry_stage_and_bindings() {
+    with_gl(|| {
+        let vs = shaders::compile(VS, gl::VERTEX_SHADER).unwrap();
+        let gs = shaders::compile(GS, gl::GEOMETRY_SHADER).unwrap();
+        let fs = shaders::compile(FS, gl::FRAGMENT_SHADER).unwrap();
+        let program = ProgramBuilder {
+            shaders: vec![&vs, &gs, &fs],
+            attribute_locations: vec![("aPos".to_owned(), 3), ("aSize".to_owned(), 5)],
+            frag_data_locations: vec![("color".to_owned(), 2)],
+            feedback_varyings: vec!["gSize".to_owned(), "gPos".to_owned()],
+            feedback_mode: FeedbackMode::Interleaved,
+            ..ProgramBuilder::default()
+        }
+        .link()
+        .unwrap();
+
+        let mut attributes: Vec<_> = program.attributes().into_iter().map(|a| (a.name, a.location)).collect();
+        attributes.sort();
+        assert_eq!(attributes, [("aPos".to_owned(), 3), ("aSize".to_owned(), 5)]);
+        if let Some(outputs) = program.outputs() {
+            let outputs: Vec<_> = outputs.into_iter().map(|o| (o.name, o.location)).collect();
+            assert_eq!(outputs, [("color".to_owned(), 2)]);
+        }
+        assert_eq!(feedback_varyings(&program), ["gSize", "gPos"]);
+
+        // Record the outputs of the geometry shader for two points
+        let mut vao = VertexArrayObject::new();
+        vao.attach_vertices(&[[0.25f32, 0.5], [-1.0, 1.0]], &[VertexAttribPointer::of::<[f32; 2]>(3)],
+                            BufferUsage::Static);
+        vao.attach_vertices(&[1.5f32, 3.0], &[VertexAttribPointer::of::<f32>(5)], BufferUsage::Static);
+        program.check_vertex_attribs(&[VertexAttribPointer::of::<[f32; 2]>(3), VertexAttribPointer::of::<f32>(5)])
+            .unwrap();
+        vao.mode = gl::POINTS;
+        let feedback = Buffer::with_capacity::<f32>(gl::TRANSFORM_FEEDBACK_BUFFER, 6, BufferUsage::Stream);
+        let mut recorded = [0f32; 6];
+        unsafe {
+            gl::UseProgram(program.id);
+            gl::Enable(gl::RASTERIZER_DISCARD);
+            gl::BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, 0, feedback.id);
+            gl::BeginTransformFeedback(gl::POINTS);
+            vao.draw();
+            gl::EndTransformFeedback();
+            gl::Disable(gl::RASTERIZER_DISCARD);
+            gl::GetBufferSubData(gl::TRANSFORM_FEEDBACK_BUFFER, 0, feedback.size() as GLsizeiptr,
+                                 recorded.as_mut_ptr() as *mut c_void);
+        }
+        assert_eq!(recorded, [3.0, 0.25, 0.5, 6.0, -1.0, 1.0]);
+    })
+    .unwrap();
+}
@@ -0,0 +1,52 @@
+//! Program binary cache tests, run in an offscreen context.
+
+use std::fs;
+use std::path::PathBuf;
+
+use learnopengl_rs::headless::with_gl;
+use learnopengl_rs::shaders::cache::ProgramCache;
+use learnopengl_rs::shaders::preprocessor::Preprocessor;
+
+fn binaries(cache: &ProgramCache) -> Vec<PathBuf> {
+    fs::read_dir(&cache.dir)
+        .map(|entries| entries.map(|e| e.unwrap().path()).collect())
+        .unwrap_or_default()
+}
+
+#[test]
+fn programs_are_cached() {
+    with_gl(|| {
+        if !ProgramCache::is_supported() {
+            eprintln!("program binaries are not supported, skipping");
+            return;
+        }
+        let cache = ProgramCache::new(PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("shader-cache"));
+        cache.clear().unwrap();
+
+        let preprocessor = Preprocessor::default();
+        let vs_src = preprocessor.preprocess_file("hello_triangle.vs").unwrap();
+        let fs_src = preprocessor.preprocess_file("hello_triangle.fs").unwrap();
+        let stages = [(&vs_src, gl::VERTEX_SHADER), (&fs_src, gl::FRAGMENT_SHADER)];
+
+        // Built from the sources, then loaded from the stored binary
+        cache.link(&stages).unwrap();
+        let stored = binaries(&cache);
+        assert_eq!(stored.len(), 1);
+        let program = cache.link(&stages).unwrap();
+        assert!(program.attributes().iter().any(|a| a.name == "aPos"));
+
+        // A rejected binary is replaced
+        fs::write(&stored[0], b"not a program binary").unwrap();
+        cache.link(&stages).unwrap();
+        assert_eq!(binaries(&cache), stored);
+        assert_ne!(fs::read(&stored[0]).unwrap(), b"not a program binary");
+
+        // Other sources get their own binary
+        let other = preprocessor.preprocess_file("hello_triangle_ex3.fs").unwrap();
+        cache.link(&[(&vs_src, gl::VERTEX_SHADER), (&other, gl::FRAGMENT_SHADER)]).unwrap();
+        assert_eq!(binaries(&cache).len(), 2);
+
+        cache.clear().unwrap();
+    })
+    .unwrap();
+}
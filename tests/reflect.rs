@@ -0,0 +1,196 @@
+//! Program reflection tests, run in an offscreen context.
+
+use gl::types::*;
+
+use learnopengl_rs::headless::with_gl;
+use learnopengl_rs::shaders::{self, ShaderProgram};
+use learnopengl_rs::textures::{self, Texture2dDescriptor, Texture2dParams};
+use learnopengl_rs::vao::packed::Packed1010102;
+use learnopengl_rs::vao::{AttributeKind, VertexAttribPointer};
+
+const VS: &str = "#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec4 aColor;
+layout (location = 2) in ivec2 aIds;
+layout (location = 3) in mat2 aTransform;
+uniform mat4 model;
+layout (std140) uniform Matrices {
+    mat4 projection;
+    mat4 view;
+};
+out vec4 vColor;
+void main() {
+    vColor = aColor + vec4(aIds, 0.0, 0.0);
+    gl_Position = projection * view * model * vec4(aTransform * aPos.xy, aPos.z, 1.0);
+}";
+
+const FS: &str = "#version 330 core
+in vec4 vColor;
+uniform sampler2D diffuse[2];
+uniform samplerCube sky;
+out vec4 FragColor;
+void main() {
+    FragColor = vColor * texture(diffuse[0], vec2(0.0)) * texture(diffuse[1], vec2(0.0)) * texture(sky, vec3(1.0));
+}";
+
+const DOUBLE_VS: &str = "#version 410 core
+layout (location = 0) in dvec2 aWide;
+out vec4 vColor;
+void main() {
+    vColor = vec4(1.0);
+    gl_Position = vec4(vec2(aWide), 0.0, 1.0);
+}";
+
+const NORMAL_VS: &str = "#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+out vec4 vColor;
+void main() {
+    vColor = vec4(aNormal, 1.0);
+    gl_Position = vec4(aPos, 1.0);
+}";
+
+/// Pointers feeding every attribute of `VS`.
+fn pointers() -> Vec<VertexAttribPointer> {
+    vec![
+        VertexAttribPointer::of::<[f32; 3]>(0),
+        VertexAttribPointer::of::<[f32; 4]>(1),
+        VertexAttribPointer { kind: AttributeKind::Integer, ..VertexAttribPointer::of::<[i32; 2]>(2) },
+        VertexAttribPointer::of::<[f32; 2]>(3),
+        VertexAttribPointer::of::<[f32; 2]>(4),
+    ]
+}
+
+/// `pointers` with the one at `index` replaced by `pointer`, or removed if it is `None`.
+fn replaced(index: GLuint, pointer: Option<VertexAttribPointer>) -> Vec<VertexAttribPointer> {
+    let mut pointers: Vec<_> = pointers().into_iter().filter(|p| p.index != index).collect();
+    pointers.extend(pointer);
+    pointers
+}
+
+fn check_attributes(prgm: &ShaderProgram) {
+    let mut attributes: Vec<_> = prgm.attributes().into_iter().map(|a| (a.name, a.ty, a.size, a.location)).collect();
+    attributes.sort();
+    assert_eq!(attributes, [
+        ("aColor".to_owned(), gl::FLOAT_VEC4, 1, 1),
+        ("aIds".to_owned(), gl::INT_VEC2, 1, 2),
+        ("aPos".to_owned(), gl::FLOAT_VEC3, 1, 0),
+        ("aTransform".to_owned(), gl::FLOAT_MAT2, 1, 3),
+    ]);
+}
+
+fn check_uniforms(prgm: &ShaderProgram) {
+    prgm.bind_uniform_block("Matrices", 3).unwrap();
+    let blocks = prgm.uniform_blocks();
+    assert_eq!(blocks.len(), 1);
+    let block = &blocks[0];
+    assert_eq!((block.name.as_str(), block.binding, block.data_size), ("Matrices", 3, 128));
+
+    let uniforms = prgm.uniforms();
+    let mut block_uniforms: Vec<_> = block.uniform_indices.iter().map(|&i| uniforms[i as usize].name.as_str()).collect();
+    block_uniforms.sort();
+    assert_eq!(block_uniforms, ["projection", "view"]);
+
+    let mut uniforms: Vec<_> = uniforms
+        .into_iter()
+        .map(|u| (u.name, u.ty, u.size, u.location >= 0, u.block_index))
+        .collect();
+    uniforms.sort();
+    assert_eq!(uniforms, [
+        ("diffuse[0]".to_owned(), gl::SAMPLER_2D, 2, true, None),
+        ("model".to_owned(), gl::FLOAT_MAT4, 1, true, None),
+        ("projection".to_owned(), gl::FLOAT_MAT4, 1, false, Some(block.index)),
+        ("sky".to_owned(), gl::SAMPLER_CUBE, 1, true, None),
+        ("view".to_owned(), gl::FLOAT_MAT4, 1, false, Some(block.index)),
+    ]);
+}
+
+fn check_vertex_attribs(prgm: &ShaderProgram) {
+    prgm.check_vertex_attribs(&pointers()).unwrap();
+
+    // GL fills the missing components
+    prgm.check_vertex_attribs(&replaced(1, Some(VertexAttribPointer::of::<[f32; 3]>(1)))).unwrap();
+
+    let integer = VertexAttribPointer { kind: AttributeKind::Integer, ..VertexAttribPointer::of::<[i32; 3]>(0) };
+    let double = VertexAttribPointer { kind: AttributeKind::Double, ..VertexAttribPointer::of::<[f64; 2]>(3) };
+    let errors = [
+        (replaced(0, Some(VertexAttribPointer::of::<[f32; 4]>(0))),
+         "vec3 aPos at location 0 has 3 components, its pointer has 4"),
+        (replaced(4, None), "no vertex attribute pointer for mat2 aTransform at location 4"),
+        (replaced(2, Some(VertexAttribPointer::of::<[i32; 2]>(2))),
+         "ivec2 aIds at location 2 reads Integer values, its pointer gives Float"),
+        (replaced(0, Some(integer)),
+         "vec3 aPos at location 0 reads Float values, its pointer gives Integer"),
+        (replaced(3, Some(double)),
+         "mat2 aTransform at location 3 reads Float values, its pointer gives Double"),
+    ];
+    for (pointers, error) in &errors {
+        assert_eq!(prgm.check_vertex_attribs(pointers), Err(error.to_string()));
+    }
+}
+
+fn check_double_attribs() {
+    let vs = shaders::compile(DOUBLE_VS, gl::VERTEX_SHADER).unwrap();
+    let fs = shaders::compile(FS, gl::FRAGMENT_SHADER).unwrap();
+    let prgm = shaders::link(&vs, &fs).unwrap();
+    let double = VertexAttribPointer { kind: AttributeKind::Double, ..VertexAttribPointer::of::<[f64; 2]>(0) };
+    prgm.check_vertex_attribs(&[double]).unwrap();
+    assert_eq!(prgm.check_vertex_attribs(&[VertexAttribPointer::of::<[f32; 2]>(0)]),
+               Err("dvec2 aWide at location 0 reads Double values, its pointer gives Float".to_owned()));
+}
+
+fn check_packed_normals() {
+    let vs = shaders::compile(NORMAL_VS, gl::VERTEX_SHADER).unwrap();
+    let fs = shaders::compile(FS, gl::FRAGMENT_SHADER).unwrap();
+    let prgm = shaders::link(&vs, &fs).unwrap();
+
+    // The packed type always has 4 components, the shader ignores w
+    let normals = VertexAttribPointer { normalized: gl::TRUE, ..VertexAttribPointer::of::<Packed1010102>(1) };
+    prgm.check_vertex_attribs(&[VertexAttribPointer::of::<[f32; 3]>(0), normals]).unwrap();
+    let unpacked = VertexAttribPointer { normalized: gl::TRUE, ..VertexAttribPointer::of::<[i8; 4]>(1) };
+    assert!(prgm.check_vertex_attribs(&[VertexAttribPointer::of::<[f32; 3]>(0), unpacked]).is_err());
+}
+
+fn check_samplers(prgm: &ShaderProgram) {
+    prgm.set_uniform("diffuse", &[0, 1][..]).unwrap();
+    prgm.set_uniform("sky", &2).unwrap();
+    assert_eq!(prgm.check_samplers(), Err([
+        "sampler2D diffuse[0] reads texture unit 0, which has no texture bound to its target",
+        "sampler2D diffuse[1] reads texture unit 1, which has no texture bound to its target",
+        "samplerCube sky reads texture unit 2, which has no texture bound to its target",
+    ].join("\n")));
+
+    let img = image::RgbaImage::new(1, 1);
+    let params = Texture2dParams::default();
+    let _diffuse = [gl::TEXTURE0, gl::TEXTURE1]
+        .map(|unit| textures::create_2d(&Texture2dDescriptor { unit, img: &img, params: &params }));
+    assert_eq!(prgm.check_samplers(), Err(
+        "samplerCube sky reads texture unit 2, which has no texture bound to its target".to_owned()));
+
+    let mut sky = 0;
+    unsafe {
+        gl::ActiveTexture(gl::TEXTURE2);
+        gl::GenTextures(1, &mut sky);
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, sky);
+    }
+    prgm.check_samplers().unwrap();
+    unsafe {
+        gl::DeleteTextures(1, &sky);
+    }
+}
+
+#[test]
+fn program_is_reflected() {
+    with_gl(|| {
+        let vs = shaders::compile(VS, gl::VERTEX_SHADER).unwrap();
+        let fs = shaders::compile(FS, gl::FRAGMENT_SHADER).unwrap();
+        let prgm = shaders::link(&vs, &fs).unwrap();
+        check_attributes(&prgm);
+        check_uniforms(&prgm);
+        check_vertex_attribs(&prgm);
+        check_double_attribs();
+        check_packed_normals();
+        check_samplers(&prgm);
+    })
+    .unwrap();
+}
@@ -0,0 +1,44 @@
+//! Shader hot-reload tests, run in an offscreen context.
+
+use std::fs;
+
+use learnopengl_rs::headless::with_gl;
+use learnopengl_rs::shaders::preprocessor::Preprocessor;
+use learnopengl_rs::shaders::reload::ReloadableProgram;
+
+const VS: &str = "#version 330 core
+#include \"position.glsl\"
+void main() {
+    gl_Position = position();
+}";
+
+const FS: &str = "#version 330 core
+out vec4 FragColor;
+void main() {
+    FragColor = vec4(1.0);
+}";
+
+#[test]
+fn missing_includes_are_watched() {
+    let dir = std::env::temp_dir().join(format!("learnopengl-reload-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    with_gl(|| {
+        fs::write(dir.join("main.vs"), VS).unwrap();
+        fs::write(dir.join("main.fs"), FS).unwrap();
+        let preprocessor = Preprocessor { include_dir: dir.clone(), ..Preprocessor::default() };
+
+        // The include is missing, so the first build fails
+        let mut program = ReloadableProgram::new(preprocessor, "main.vs", "main.fs");
+        assert_eq!(program.program().id, 0);
+        assert!(!program.reload_if_modified().unwrap());
+
+        // Creating it triggers a reload
+        fs::write(dir.join("position.glsl"), "vec4 position() { return vec4(0.0); }").unwrap();
+        assert!(program.reload_if_modified().unwrap());
+        assert_ne!(program.program().id, 0);
+        assert!(!program.reload_if_modified().unwrap());
+    })
+    .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
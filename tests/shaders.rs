@@ -0,0 +1,40 @@
+//! Offline validation of the shaders in `res/shaders`, which does not need a GPU.
+
+use learnopengl_rs::shaders::preprocessor::Preprocessor;
+use learnopengl_rs::shaders::validate::{self, Validation};
+
+/// Vertex and fragment shaders linked together by the examples.
+const PROGRAMS: &[(&str, &str)] = &[
+    ("coordinate_systems.vs", "textures_multi.fs"),
+    ("hello_triangle.vs", "hello_triangle.fs"),
+    ("hello_triangle.vs", "hello_triangle_ex3.fs"),
+    ("shaders.vs", "shaders.fs"),
+    ("shaders_ex1.vs", "shaders.fs"),
+    ("shaders_ex2.vs", "shaders.fs"),
+    ("shaders_ex3.vs", "shaders_ex3.fs"),
+    ("textures.vs", "textures.fs"),
+    ("textures_multi.vs", "textures_ex1.fs"),
+    ("textures_multi.vs", "textures_ex4.fs"),
+    ("textures_multi.vs", "textures_multi.fs"),
+    ("transformations.vs", "textures_multi.fs"),
+];
+
+#[test]
+fn shaders_are_valid() {
+    match validate::validate_all(&Preprocessor::default()) {
+        Ok(count) => assert!(count > 0, "no shader found"),
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[test]
+fn program_interfaces_match() {
+    let preprocessor = Preprocessor::default();
+    for (vs, fs) in PROGRAMS {
+        match validate::validate_program(&preprocessor, vs, fs) {
+            Ok(Validation::Complete) => {}
+            Ok(Validation::Skipped(warnings)) => panic!("{} + {} not validated: {:?}", vs, fs, warnings),
+            Err(e) => panic!("{} + {}: {}", vs, fs, e),
+        }
+    }
+}
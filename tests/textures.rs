@@ -0,0 +1,247 @@
+//! Texture loading tests, run in an offscreen context.
+
+use std::ffi::c_void;
+
+use gl::types::*;
+use image::{DynamicImage, ImageBuffer, Luma, LumaA, Rgb, Rgba};
+
+use learnopengl_rs::buffer::BufferUsage;
+use learnopengl_rs::context::has_feature;
+use learnopengl_rs::headless::{run_headless, with_gl};
+use learnopengl_rs::shaders::{self, ShaderProgram};
+use learnopengl_rs::textures::{
+    DepthCompare, MagFilter, MinFilter, Texture2d, Texture2dOptions, Texture2dParams, TextureError, Wrap,
+};
+use learnopengl_rs::vao::{VertexArrayObject, VertexAttribPointer};
+use learnopengl_rs::OpenGLApp;
+
+const SHADOW_VS: &str = "#version 330 core
+layout (location = 0) in vec2 aPos;
+void main() {
+    gl_Position = vec4(aPos, 0.0, 1.0);
+}";
+
+/// Compares the depths of the texels of a 2x1 texture with 0.5.
+const SHADOW_FS: &str = "#version 330 core
+uniform sampler2DShadow shadowMap;
+out vec4 FragColor;
+void main() {
+    FragColor = vec4(texture(shadowMap, vec3(gl_FragCoord.xy / vec2(2.0, 1.0), 0.5)));
+}";
+
+/// Internal format of the level 0 of `texture`.
+fn internal_format(texture: &Texture2d) -> GLenum {
+    let mut format = 0;
+    unsafe {
+        gl::BindTexture(gl::TEXTURE_2D, texture.id);
+        gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_INTERNAL_FORMAT, &mut format);
+    }
+    format as GLenum
+}
+
+/// Read the level 0 of `texture` back as `len` values of type `T`.
+fn texels<T: Copy + Default>(texture: &Texture2d, format: GLenum, ty: GLenum, len: usize) -> Vec<T> {
+    let mut data = vec![T::default(); len];
+    unsafe {
+        gl::BindTexture(gl::TEXTURE_2D, texture.id);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::GetTexImage(gl::TEXTURE_2D, 0, format, ty, data.as_mut_ptr() as *mut c_void);
+    }
+    data
+}
+
+/// Width of the mipmap `level` of `texture`, 0 if it is not defined.
+fn level_width(texture: &Texture2d, level: GLint) -> GLint {
+    let mut width = 0;
+    unsafe {
+        gl::BindTexture(gl::TEXTURE_2D, texture.id);
+        gl::GetTexLevelParameteriv(gl::TEXTURE_2D, level, gl::TEXTURE_WIDTH, &mut width);
+    }
+    width
+}
+
+/// Parameter `pname` of the texture bound to `GL_TEXTURE_2D`.
+fn parameter(pname: GLenum) -> GLint {
+    let mut value = 0;
+    unsafe {
+        gl::GetTexParameteriv(gl::TEXTURE_2D, pname, &mut value);
+    }
+    value
+}
+
+fn parameter_f(pname: GLenum) -> f32 {
+    let mut value = 0.0;
+    unsafe {
+        gl::GetTexParameterfv(gl::TEXTURE_2D, pname, &mut value);
+    }
+    value
+}
+
+fn load(img: DynamicImage, flip_vertically: bool, srgb: bool) -> Texture2d {
+    let options = Texture2dOptions { flip_vertically, srgb, ..Texture2dOptions::default() };
+    Texture2d::from_dynamic_image(&img, &options).unwrap()
+}
+
+/// Samples a depth texture holding 0.25 and 0.75 with a shadow sampler comparing them to 0.5.
+struct ShadowApp {
+    compare: DepthCompare,
+    texture: Option<Texture2d>,
+    vao: VertexArrayObject,
+    prgm: ShaderProgram,
+}
+
+impl ShadowApp {
+    fn new(compare: DepthCompare) -> Self {
+        Self { compare, texture: None, vao: VertexArrayObject::default(), prgm: ShaderProgram::default() }
+    }
+}
+
+impl OpenGLApp for ShadowApp {
+    fn width(&self) -> f32 {
+        2.0
+    }
+
+    fn height(&self) -> f32 {
+        1.0
+    }
+
+    fn initialize(&mut self) {
+        // Linear filtering may average the comparisons of neighbouring texels
+        let params = Texture2dParams {
+            min_filter: MinFilter::Nearest,
+            mag_filter: MagFilter::Nearest,
+            depth_compare: Some(self.compare),
+            ..Texture2dParams::default()
+        };
+        let texture = Texture2d::depth(2, 1, gl::TEXTURE0, &params).unwrap();
+        assert_eq!(internal_format(&texture), gl::DEPTH_COMPONENT24);
+        let depths = [0.25f32, 0.75];
+        unsafe {
+            gl::TexSubImage2D(gl::TEXTURE_2D, 0, 0, 0, 2, 1, gl::DEPTH_COMPONENT, gl::FLOAT,
+                              depths.as_ptr() as *const c_void);
+        }
+        self.texture = Some(texture);
+
+        let vs = shaders::compile(SHADOW_VS, gl::VERTEX_SHADER).unwrap();
+        let fs = shaders::compile(SHADOW_FS, gl::FRAGMENT_SHADER).unwrap();
+        self.prgm = shaders::link(&vs, &fs).unwrap();
+        self.prgm.set_uniform("shadowMap", &0).unwrap();
+        self.vao = VertexArrayObject::new();
+        self.vao.attach_vertices(&[[-1.0f32, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]],
+                                 &[VertexAttribPointer::of::<[f32; 2]>(0)], BufferUsage::Static);
+        self.vao.mode = gl::TRIANGLE_STRIP;
+    }
+
+    fn render(&self, _alpha: f32) {
+        unsafe {
+            gl::UseProgram(self.prgm.id);
+        }
+        self.prgm.check_samplers().unwrap();
+        self.vao.draw();
+    }
+}
+
+/// Whether the comparison passes for the texels holding 0.25 and 0.75.
+fn shadow_comparisons(compare: DepthCompare) -> [bool; 2] {
+    let frames = run_headless(ShadowApp::new(compare), 1).unwrap();
+    [0, 1].map(|x| frames[0].get_pixel(x, 0)[0] == 255)
+}
+
+#[test]
+fn textures_are_loaded() {
+    with_gl(|| {
+        // Rows of 3 bytes, flipped
+        let gray = ImageBuffer::from_raw(3, 2, vec![1u8, 2, 3, 4, 5, 6]).unwrap();
+        let texture = load(DynamicImage::ImageLuma8(gray), true, false);
+        assert_eq!(internal_format(&texture), gl::R8);
+        assert_eq!(texels::<u8>(&texture, gl::RED, gl::UNSIGNED_BYTE, 6), [4, 5, 6, 1, 2, 3]);
+        let mut swizzle = [0; 4];
+        unsafe {
+            gl::GetTexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_mut_ptr());
+        }
+        assert_eq!(swizzle.map(|c| c as GLenum), [gl::RED, gl::RED, gl::RED, gl::ONE]);
+
+        let gray_alpha = ImageBuffer::<LumaA<u8>, _>::from_raw(1, 1, vec![7, 8]).unwrap();
+        assert_eq!(internal_format(&load(DynamicImage::ImageLumaA8(gray_alpha), false, false)), gl::RG8);
+
+        let rgb = ImageBuffer::<Rgb<u8>, _>::from_raw(3, 1, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]).unwrap();
+        let texture = load(DynamicImage::ImageRgb8(rgb.clone()), false, false);
+        assert_eq!(internal_format(&texture), gl::RGB8);
+        assert_eq!(texels::<u8>(&texture, gl::RGB, gl::UNSIGNED_BYTE, 9), rgb.as_raw()[..]);
+        assert_eq!(internal_format(&load(DynamicImage::ImageRgb8(rgb), false, true)), gl::SRGB8);
+
+        let rgba16 = ImageBuffer::<Rgba<u16>, _>::from_raw(1, 1, vec![0x1234, 0x5678, 0x9abc, 0xffff]).unwrap();
+        let texture = load(DynamicImage::ImageRgba16(rgba16), false, false);
+        assert_eq!(internal_format(&texture), gl::RGBA16);
+        assert_eq!(texels::<u16>(&texture, gl::RGBA, gl::UNSIGNED_SHORT, 4), [0x1234, 0x5678, 0x9abc, 0xffff]);
+
+        // sRGB textures only have 8-bit color channels
+        let gray16 = ImageBuffer::<Luma<u16>, _>::from_raw(1, 1, vec![0xffff]).unwrap();
+        let texture = load(DynamicImage::ImageLuma16(gray16.clone()), false, false);
+        assert_eq!(internal_format(&texture), gl::R16);
+        let texture = load(DynamicImage::ImageLuma16(gray16), false, true);
+        assert_eq!(internal_format(&texture), gl::SRGB8);
+        assert_eq!(texels::<u8>(&texture, gl::RGB, gl::UNSIGNED_BYTE, 3), [255, 255, 255]);
+
+        let texture = Texture2d::from_path("res/textures/awesomeface.png", &Texture2dOptions::default()).unwrap();
+        assert_eq!(internal_format(&texture), gl::RGBA8);
+        match Texture2d::from_path("res/textures/missing.png", &Texture2dOptions::default()) {
+            Err(error @ TextureError::Image { .. }) => {
+                assert!(error.to_string().starts_with("failed to load res/textures/missing.png"))
+            }
+            other => panic!("expected an image error, got {:?}", other.map(|t| t.id)),
+        }
+    })
+    .unwrap();
+}
+
+#[test]
+fn parameters_are_applied() {
+    with_gl(|| {
+        let img = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(4, 4, Rgba([255, 0, 0, 255])));
+        let texture = load(img.clone(), false, false);
+        assert_eq!((level_width(&texture, 0), level_width(&texture, 1)), (4, 0));
+
+        let params = Texture2dParams {
+            s_mode: Wrap::ClampToBorder,
+            t_mode: Wrap::MirroredRepeat,
+            min_filter: MinFilter::LinearMipmapLinear,
+            mag_filter: MagFilter::Nearest,
+            lod_bias: -0.5,
+            min_lod: 1.0,
+            max_lod: 2.0,
+            border_color: [0.0, 0.5, 1.0, 1.0],
+            max_anisotropy: 1000.0,
+            depth_compare: Some(DepthCompare::LessEqual),
+        };
+        let texture = Texture2d::from_dynamic_image(&img, &Texture2dOptions { params, ..Texture2dOptions::default() })
+            .unwrap();
+        assert_eq!([1, 2].map(|level| level_width(&texture, level)), [2, 1]);
+        assert_eq!(parameter(gl::TEXTURE_WRAP_S), gl::CLAMP_TO_BORDER as GLint);
+        assert_eq!(parameter(gl::TEXTURE_WRAP_T), gl::MIRRORED_REPEAT as GLint);
+        assert_eq!(parameter(gl::TEXTURE_MIN_FILTER), gl::LINEAR_MIPMAP_LINEAR as GLint);
+        assert_eq!(parameter(gl::TEXTURE_MAG_FILTER), gl::NEAREST as GLint);
+        assert_eq!([gl::TEXTURE_LOD_BIAS, gl::TEXTURE_MIN_LOD, gl::TEXTURE_MAX_LOD].map(parameter_f), [-0.5, 1.0, 2.0]);
+        let mut border = [0.0; 4];
+        unsafe {
+            gl::GetTexParameterfv(gl::TEXTURE_2D, gl::TEXTURE_BORDER_COLOR, border.as_mut_ptr());
+        }
+        assert_eq!(border, [0.0, 0.5, 1.0, 1.0]);
+        assert_eq!(parameter(gl::TEXTURE_COMPARE_MODE), gl::COMPARE_REF_TO_TEXTURE as GLint);
+        assert_eq!(parameter(gl::TEXTURE_COMPARE_FUNC), gl::LEQUAL as GLint);
+        if has_feature((4, 6), "GL_EXT_texture_filter_anisotropic") {
+            // Clamped to the maximum of the driver
+            let anisotropy = parameter_f(0x84FE);
+            assert!(anisotropy > 1.0 && anisotropy < 1000.0, "anisotropy {}", anisotropy);
+        }
+    })
+    .unwrap();
+}
+
+#[test]
+fn depth_textures_are_compared() {
+    // The reference 0.5 is compared to the texel
+    assert_eq!(shadow_comparisons(DepthCompare::LessEqual), [false, true]);
+    assert_eq!(shadow_comparisons(DepthCompare::Greater), [true, false]);
+    assert_eq!(shadow_comparisons(DepthCompare::Always), [true, true]);
+}
@@ -0,0 +1,228 @@
+//! Vertex array drawing tests, rendering small scenes offscreen.
+
+use gl::types::*;
+use image::RgbaImage;
+
+use learnopengl_rs::buffer::BufferUsage;
+use learnopengl_rs::headless::run_headless;
+use learnopengl_rs::shaders::{self, ShaderProgram};
+use learnopengl_rs::vao::packed::{self, Packed1010102};
+use learnopengl_rs::vao::{self, AttributeKind, Vertex as _, VertexArrayObject, VertexAttribPointer};
+use learnopengl_rs::OpenGLApp;
+
+const VS: &str = "#version 330 core
+layout (location = 0) in vec2 aPos;
+layout (location = 1) in float aShift;
+void main() {
+    // Instances are laid out from left to right
+    gl_Position = vec4(aPos.x + aShift + 0.5 * gl_InstanceID, aPos.y, 0.0, 1.0);
+}";
+
+const FS: &str = "#version 330 core
+out vec4 FragColor;
+void main() {
+    FragColor = vec4(1.0);
+}";
+
+/// Shifts the quad by 1 if `aId` is read exactly, as it is not representable as a float, and
+/// by 0.5 if `aWide` is, for the same reason.
+const EXACT_VS: &str = "#version 410 core
+layout (location = 0) in vec2 aPos;
+layout (location = 1) in int aId;
+layout (location = 2) in double aWide;
+void main() {
+    float shift = (aId == 16777217 ? 1.0 : 0.0) + (aWide > 1.0lf ? 0.5 : 0.0);
+    gl_Position = vec4(aPos.x + shift, aPos.y, 0.0, 1.0);
+}";
+
+#[derive(vao::Vertex)]
+#[repr(C)]
+struct Vertex {
+    pos: [f32; 2],
+}
+
+/// A vertex array drawn by `draw`.
+struct DrawApp<F: Fn(&VertexArrayObject)> {
+    vs: &'static str,
+    make_vao: fn() -> VertexArrayObject,
+    draw: F,
+    vao: VertexArrayObject,
+    prgm: ShaderProgram,
+}
+
+impl<F: Fn(&VertexArrayObject)> OpenGLApp for DrawApp<F> {
+    fn width(&self) -> f32 {
+        8.0
+    }
+
+    fn height(&self) -> f32 {
+        8.0
+    }
+
+    fn initialize(&mut self) {
+        let vs = shaders::compile(self.vs, gl::VERTEX_SHADER).unwrap();
+        let fs = shaders::compile(FS, gl::FRAGMENT_SHADER).unwrap();
+        self.prgm = shaders::link(&vs, &fs).unwrap();
+        self.vao = (self.make_vao)();
+    }
+
+    fn render(&self, _alpha: f32) {
+        unsafe {
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+            gl::UseProgram(self.prgm.id);
+        }
+        (self.draw)(&self.vao);
+    }
+}
+
+/// Render the vertex array and return which of the 8 columns of the frame are partly covered.
+fn columns(make_vao: fn() -> VertexArrayObject, draw: impl Fn(&VertexArrayObject)) -> Vec<bool> {
+    columns_with(VS, make_vao, draw)
+}
+
+/// `columns` with another vertex shader.
+fn columns_with(vs: &'static str, make_vao: fn() -> VertexArrayObject, draw: impl Fn(&VertexArrayObject)) -> Vec<bool> {
+    let app = DrawApp { vs, make_vao, draw, vao: VertexArrayObject::default(), prgm: ShaderProgram::default() };
+    let frames: Vec<RgbaImage> = run_headless(app, 1).unwrap();
+    (0..8).map(|x| (0..8).any(|y| frames[0].get_pixel(x, y)[0] == 255)).collect()
+}
+
+/// A quad covering the column at `x` in [-1, 1], as a triangle strip.
+fn quad(x: f32) -> [Vertex; 4] {
+    [
+        Vertex { pos: [x, -1.0] },
+        Vertex { pos: [x + 0.25, -1.0] },
+        Vertex { pos: [x, 1.0] },
+        Vertex { pos: [x + 0.25, 1.0] },
+    ]
+}
+
+/// Quads at the columns 0, 2 and 4 as triangle strips separated by restart indices.
+///
+/// Vertex 255 is at the right edge, so that triangles using the restart index of `u8` as a
+/// vertex would show up.
+fn strips<I: vao::Index + Copy>(indices: &[I]) -> VertexArrayObject {
+    let mut vertices: Vec<Vertex> = [-1.0, -0.5, 0.0].iter().flat_map(|x| quad(*x)).collect();
+    vertices.resize_with(255, || Vertex { pos: [0.0, 0.0] });
+    vertices.push(Vertex { pos: [1.0, 0.0] });
+    let mut vao = vao::create_indexed(&vertices, &Vertex::attributes(), indices);
+    vao.mode = gl::TRIANGLE_STRIP;
+    vao.primitive_restart = true;
+    vao
+}
+
+#[test]
+fn draw_indexed_strips() {
+    let make_u8 = || strips(&[0u8, 1, 2, 3, u8::MAX, 4, 5, 6, 7, u8::MAX, 8, 9, 10, 11]);
+    let make_u16 = || strips(&[0u16, 1, 2, 3, u16::MAX, 4, 5, 6, 7, u16::MAX, 8, 9, 10, 11]);
+    let make_u32 = || strips(&[0u32, 1, 2, 3, u32::MAX, 4, 5, 6, 7, u32::MAX, 8, 9, 10, 11]);
+    let expected = [true, false, true, false, true, false, false, false];
+    for make_vao in [make_u8 as fn() -> _, make_u16, make_u32] {
+        assert_eq!(columns(make_vao, |vao| {
+            assert_eq!((vao.vertex_count(), vao.index_count(), vao.count()), (256, 14, 14));
+            vao.draw();
+        }), expected);
+    }
+    // The second strip only
+    assert_eq!(columns(make_u16, |vao| vao.draw_range(5, 4)),
+               [false, false, true, false, false, false, false, false]);
+}
+
+#[test]
+fn draw_arrays_instanced() {
+    let make_vao = || vao::create(&quad(-1.0), &Vertex::attributes());
+    assert_eq!(columns(make_vao, |vao| {
+        assert!(!vao.is_indexed());
+        assert_eq!(vao.count(), 4);
+        vao.draw_instanced(3);
+    }), [true, false, true, false, true, false, false, false]);
+}
+
+/// The enabled state and divisor of the attribute at `index` of the bound vertex array.
+fn attrib_state(index: GLuint) -> (GLint, GLint) {
+    let (mut enabled, mut divisor) = (0, 0);
+    unsafe {
+        gl::GetVertexAttribiv(index, gl::VERTEX_ATTRIB_ARRAY_ENABLED, &mut enabled);
+        gl::GetVertexAttribiv(index, gl::VERTEX_ATTRIB_ARRAY_DIVISOR, &mut divisor);
+    }
+    (enabled, divisor)
+}
+
+#[test]
+fn replace_instances() {
+    // The shifts of the first buffer would move the quads by a column
+    let make_vao = || {
+        let mut vao = vao::create(&quad(-1.0), &Vertex::attributes());
+        vao.attach_instances(&[0.25f32; 2], &[VertexAttribPointer::of::<f32>(1)], BufferUsage::Static);
+        vao.attach_instances(&[0.0f32; 2], &[VertexAttribPointer::of::<f32>(2)], BufferUsage::Static);
+        vao
+    };
+    assert_eq!(columns(make_vao, |vao| {
+        assert_eq!(vao.instance_count(), 2);
+        vao.draw_instanced(2);
+        assert_eq!(attrib_state(1), (gl::FALSE as GLint, 0));
+        assert_eq!(attrib_state(2), (gl::TRUE as GLint, 1));
+    }), [true, false, true, false, false, false, false, false]);
+}
+
+#[test]
+fn draw_separate_buffers() {
+    // Positions and shifts in their own buffers, indexed
+    let make_vao = || {
+        let positions = quad(-1.0).map(|v| v.pos);
+        let mut vao = VertexArrayObject::new();
+        vao.attach_vertices(&positions, &[VertexAttribPointer::of::<[f32; 2]>(0)], BufferUsage::Static);
+        vao.attach_vertices(&[1.0f32; 4], &[VertexAttribPointer::of::<f32>(1)], BufferUsage::Static);
+        vao.attach_indices(&[0u16, 1, 2, 2, 1, 3], BufferUsage::Static);
+        vao
+    };
+    assert_eq!(columns(make_vao, |vao| {
+        assert_eq!((vao.buffers.len(), vao.vertex_count(), vao.count()), (1, 4, 6));
+        vao.draw();
+    }), [false, false, false, false, true, false, false, false]);
+}
+
+#[test]
+fn draw_integer_and_double_attributes() {
+    let make_vao = || {
+        let positions = quad(-1.0).map(|v| v.pos);
+        let id = VertexAttribPointer { kind: AttributeKind::Integer, ..VertexAttribPointer::of::<i32>(1) };
+        let wide = VertexAttribPointer { kind: AttributeKind::Double, ..VertexAttribPointer::of::<f64>(2) };
+        let mut vao = VertexArrayObject::new();
+        vao.attach_vertices(&positions, &[VertexAttribPointer::of::<[f32; 2]>(0)], BufferUsage::Static);
+        vao.attach_vertices(&[16777217i32; 4], &[id], BufferUsage::Static);
+        vao.attach_vertices(&[1.0 + 1e-12f64; 4], &[wide], BufferUsage::Static);
+        vao
+    };
+    assert_eq!(columns_with(EXACT_VS, make_vao, |vao| vao.draw()),
+               [false, false, false, false, false, false, true, false]);
+}
+
+#[test]
+fn draw_packed_attributes() {
+    // Half float positions, shifted by 1 read from normalized integers
+    fn make_vao(shifts: impl FnOnce(&mut VertexArrayObject)) -> VertexArrayObject {
+        let positions = packed::to_f16(&quad(-1.0).map(|v| v.pos));
+        let mut vao = VertexArrayObject::new();
+        vao.attach_vertices(&positions, &[VertexAttribPointer::of::<[packed::f16; 2]>(0)], BufferUsage::Static);
+        shifts(&mut vao);
+        vao
+    }
+    fn normalized<T: vao::VertexAttribute>() -> VertexAttribPointer {
+        VertexAttribPointer { normalized: gl::TRUE, ..VertexAttribPointer::of::<T>(1) }
+    }
+    let make_i8 = || make_vao(|vao| {
+        vao.attach_vertices(&packed::to_snorm8(&[[1.0]; 4]), &[normalized::<i8>()], BufferUsage::Static)
+    });
+    let make_i16 = || make_vao(|vao| {
+        vao.attach_vertices(&packed::to_snorm16(&[[1.0]; 4]), &[normalized::<i16>()], BufferUsage::Static)
+    });
+    let make_1010102 = || make_vao(|vao| {
+        let shifts = packed::to_packed_normals(&[[1.0, 0.0, 0.0]; 4]);
+        vao.attach_vertices(&shifts, &[normalized::<Packed1010102>()], BufferUsage::Static)
+    });
+    for make_vao in [make_i8 as fn() -> _, make_i16, make_1010102] {
+        assert_eq!(columns(make_vao, |vao| vao.draw()), [false, false, false, false, true, false, false, false]);
+    }
+}